@@ -0,0 +1,315 @@
+//! Brains for creatures. Each tick, a creature's `Behavior` looks at the
+//! world and says what the creature should do; small behaviours combine into
+//! bigger ones with `Priority` and `Sequence`, a simple behaviour tree.
+
+use crate::game::{Creature, Direction};
+use crate::rand::{Rand, Rng};
+use crate::world::{CreatureId, World};
+
+/// What a creature does with one tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    /// Turn this way and keep moving.
+    Move(Direction),
+    /// Stay put.
+    Wait,
+}
+
+/// A read-only view of the world from one creature's point of view.
+#[derive(Clone, Copy)]
+pub struct WorldView<'a> {
+    world: &'a World,
+    me: CreatureId,
+}
+
+impl<'a> WorldView<'a> {
+    pub fn new(world: &'a World, me: CreatureId) -> WorldView<'a> {
+        WorldView { world, me }
+    }
+
+    pub fn world(&self) -> &'a World {
+        self.world
+    }
+
+    /// The creature doing the looking.
+    pub fn me(&self) -> CreatureId {
+        self.me
+    }
+
+    pub fn creature(&self) -> &'a dyn Creature {
+        self.world.creature(self.me).expect("viewing the world as a removed creature")
+    }
+
+    pub fn position(&self) -> (i32, i32) {
+        self.creature().position()
+    }
+
+    pub fn facing(&self) -> Direction {
+        self.creature().facing()
+    }
+
+    pub fn can_move(&self, direction: Direction) -> bool {
+        self.world.can_move(self.me, direction)
+    }
+
+    /// Where `other` is, if it's still in the world.
+    pub fn position_of(&self, other: CreatureId) -> Option<(i32, i32)> {
+        self.world.creature(other).map(|c| c.position())
+    }
+}
+
+/// Decides what a creature does, tick by tick.
+pub trait Behavior {
+    /// What to do this tick, or `None` if this behaviour has nothing to
+    /// suggest (or has finished). Without any suggestion the creature keeps
+    /// going the way it faces.
+    fn decide(&mut self, view: &WorldView, rng: &mut dyn Rng) -> Option<Action>;
+}
+
+impl<B: Behavior + ?Sized> Behavior for Box<B> {
+    fn decide(&mut self, view: &WorldView, rng: &mut dyn Rng) -> Option<Action> {
+        (**self).decide(view, rng)
+    }
+}
+
+/// Ask each child in turn, and do what the first with a suggestion says.
+pub struct Priority(pub Vec<Box<dyn Behavior>>);
+
+impl Behavior for Priority {
+    fn decide(&mut self, view: &WorldView, rng: &mut dyn Rng) -> Option<Action> {
+        self.0.iter_mut().find_map(|child| child.decide(view, rng))
+    }
+}
+
+/// Follow each child until it has nothing more to suggest, then move on to
+/// the next. A looping sequence starts over after the last child; otherwise
+/// it finishes.
+pub struct Sequence {
+    children: Vec<Box<dyn Behavior>>,
+    current: usize,
+    looping: bool,
+}
+
+impl Sequence {
+    pub fn new(children: Vec<Box<dyn Behavior>>) -> Sequence {
+        Sequence { children, current: 0, looping: false }
+    }
+
+    pub fn looping(mut self) -> Sequence {
+        self.looping = true;
+        self
+    }
+}
+
+impl Behavior for Sequence {
+    fn decide(&mut self, view: &WorldView, rng: &mut dyn Rng) -> Option<Action> {
+        // Give each child one chance per tick, so a loop of children that
+        // are all finished can't spin forever.
+        for _ in 0..self.children.len() {
+            if self.current == self.children.len() {
+                if !self.looping {
+                    return None;
+                }
+                self.current = 0;
+            }
+            if let Some(action) = self.children[self.current].decide(view, rng) {
+                return Some(action);
+            }
+            self.current += 1;
+        }
+        None
+    }
+}
+
+/// Take `steps` turns moving `direction`, then finish. Each turn is used up
+/// even if the creature is blocked.
+pub struct Walk {
+    pub direction: Direction,
+    pub steps: usize,
+    taken: usize,
+}
+
+impl Walk {
+    pub fn new(direction: Direction, steps: usize) -> Walk {
+        Walk { direction, steps, taken: 0 }
+    }
+}
+
+impl Behavior for Walk {
+    fn decide(&mut self, _view: &WorldView, _rng: &mut dyn Rng) -> Option<Action> {
+        if self.taken == self.steps {
+            self.taken = 0;
+            return None;
+        }
+        self.taken += 1;
+        Some(Action::Move(self.direction))
+    }
+}
+
+/// Wander about, keeping a heading for a while and then picking a new one
+/// at random. Never walks into anything.
+pub struct Wander {
+    /// The chance of a new heading each tick, from 0 to 1.
+    pub restlessness: f64,
+}
+
+impl Behavior for Wander {
+    fn decide(&mut self, view: &WorldView, rng: &mut dyn Rng) -> Option<Action> {
+        let mut rng = rng;
+        if f64::rand(&mut rng) >= self.restlessness && view.can_move(view.facing()) {
+            return Some(Action::Move(view.facing()));
+        }
+        let open: Vec<Direction> = Direction::ALL.iter().copied()
+            .filter(|&d| view.can_move(d))
+            .collect();
+        if open.is_empty() {
+            return Some(Action::Wait);
+        }
+        Some(Action::Move(open[rng.below(open.len() as u64) as usize]))
+    }
+}
+
+/// Chebyshev distance: the number of 8-way steps between two cells.
+fn steps_between((x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> i32 {
+    (x1 - x0).abs().max((y1 - y0).abs())
+}
+
+/// The open direction whose first step best serves `score`, lowest first;
+/// ties go to the direction nearest to north, clockwise.
+fn best_step<F>(view: &WorldView, score: F) -> Option<Direction>
+    where F: Fn((i32, i32)) -> i32
+{
+    let (x, y) = view.position();
+    Direction::ALL.iter().copied()
+        .filter(|&d| view.can_move(d))
+        .min_by_key(|d| {
+            let (dx, dy) = d.delta();
+            score((x + dx, y + dy))
+        })
+}
+
+/// Head for `target` while it's within `range` steps.
+pub struct Chase {
+    pub target: CreatureId,
+    pub range: i32,
+}
+
+impl Behavior for Chase {
+    fn decide(&mut self, view: &WorldView, _rng: &mut dyn Rng) -> Option<Action> {
+        let target = view.position_of(self.target)?;
+        let distance = steps_between(view.position(), target);
+        if distance > self.range {
+            return None;
+        }
+        match best_step(view, |p| steps_between(p, target)) {
+            Some(d) if distance > 1 => Some(Action::Move(d)),
+            _ => Some(Action::Wait),
+        }
+    }
+}
+
+/// Run from `threat` while it's within `range` steps.
+pub struct Flee {
+    pub threat: CreatureId,
+    pub range: i32,
+}
+
+impl Behavior for Flee {
+    fn decide(&mut self, view: &WorldView, _rng: &mut dyn Rng) -> Option<Action> {
+        let threat = view.position_of(self.threat)?;
+        if steps_between(view.position(), threat) > self.range {
+            return None;
+        }
+        match best_step(view, |p| -steps_between(p, threat)) {
+            Some(d) => Some(Action::Move(d)),
+            None => Some(Action::Wait),
+        }
+    }
+}
+
+/// Sweep back and forth along a row: east up to `span` cells from where the
+/// sweep began, then west back to the start, turning early if blocked.
+pub struct Sweep {
+    pub span: i32,
+    start: Option<i32>,
+}
+
+impl Sweep {
+    pub fn new(span: i32) -> Sweep {
+        Sweep { span, start: None }
+    }
+}
+
+impl Behavior for Sweep {
+    fn decide(&mut self, view: &WorldView, _rng: &mut dyn Rng) -> Option<Action> {
+        let (x, _) = view.position();
+        let start = *self.start.get_or_insert(x);
+        let mut heading = match view.facing() {
+            Direction::West => Direction::West,
+            _ => Direction::East,
+        };
+        let at_end = match heading {
+            Direction::East => x >= start + self.span,
+            _ => x <= start,
+        };
+        if at_end || !view.can_move(heading) {
+            heading = heading.opposite();
+            if !view.can_move(heading) {
+                return Some(Action::Wait);
+            }
+        }
+        Some(Action::Move(heading))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Broom;
+
+    fn track(world: &mut World, id: CreatureId, ticks: usize) -> Vec<i32> {
+        (0..ticks).map(|_| {
+            world.tick();
+            world.creature(id).unwrap().position().0
+        }).collect()
+    }
+
+    #[test]
+    fn test_sweeping_patrol() {
+        let mut world = World::new(12, 5, 3);
+        let broom = world.add_creature(Broom::new(2, 4, 1), 10.0);
+        world.set_behavior(broom, Broom::sweeping_patrol(3));
+        assert_eq!(track(&mut world, broom, 8), [3, 4, 5, 4, 3, 2, 3, 4]);
+
+        // A wall at x = 5 cuts the sweep short, turning it back at 4.
+        world.add_obstacle(Broom::new(5, 4, 0));
+        assert_eq!(track(&mut world, broom, 4), [3, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let mut world = World::new(20, 20, 11);
+        let cat = world.add_creature(Broom::new(10, 10, 0), 10.0);
+        let mouse = world.add_creature(Broom::new(13, 10, 0), 10.0);
+        world.set_behavior(cat, Sequence::new(vec![
+            Box::new(Walk::new(Direction::North, 2)),
+            Box::new(Chase { target: mouse, range: 10 }),
+        ]));
+        world.set_behavior(mouse, Priority(vec![
+            Box::new(Flee { threat: cat, range: 3 }),
+            Box::new(Wander { restlessness: 0.3 }),
+        ]));
+
+        let (x, y) = world.creature(cat).unwrap().position();
+        world.tick();
+        world.tick();
+        assert_eq!(world.creature(cat).unwrap().position(), (x, y - 2));
+        for _ in 0..60 {
+            world.tick();
+        }
+        // The cat closes in while the mouse runs out of room.
+        let cat_at = world.creature(cat).unwrap().position();
+        let mouse_at = world.creature(mouse).unwrap().position();
+        assert!(steps_between(cat_at, mouse_at) <= 2, "{:?} {:?}", cat_at, mouse_at);
+    }
+}
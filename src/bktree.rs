@@ -0,0 +1,161 @@
+//! A Burkhard-Keller tree, for fuzzy lookup in large dictionaries under an
+//! integer-valued metric such as Levenshtein, Damerau-Levenshtein or Hamming.
+
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+use crate::distance::{resolve, Collector, KBest, MeasureDistance, Within};
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// Index into `BkTree::items`.
+    item: usize,
+    /// Children keyed by their distance from this node's item.
+    children: BTreeMap<usize, usize>,
+}
+
+/// A BK-tree. Every child hangs off its parent under the distance between
+/// them, so a search for items within `d` of a target at distance `n` from a
+/// node only needs the children keyed `n - d ..= n + d`.
+///
+/// Results match the brute-force searches in `distance` on the items in
+/// insertion order, ties included.
+#[derive(Clone, Debug)]
+pub struct BkTree<T> {
+    items: Vec<T>,
+    nodes: Vec<Node>,
+}
+
+impl<T: MeasureDistance<Distance=usize>> Default for BkTree<T> {
+    fn default() -> Self {
+        BkTree::new()
+    }
+}
+
+impl<T: MeasureDistance<Distance=usize>> BkTree<T> {
+    pub fn new() -> BkTree<T> {
+        BkTree { items: vec![], nodes: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// The items in insertion order.
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn insert(&mut self, item: T) {
+        let index = self.items.len();
+        let new_node = self.nodes.len();
+        if !self.nodes.is_empty() {
+            let mut n = 0;
+            loop {
+                let d = item.distance(&self.items[self.nodes[n].item]);
+                match self.nodes[n].children.get(&d) {
+                    Some(&child) => n = child,
+                    None => {
+                        self.nodes[n].children.insert(d, new_node);
+                        break;
+                    }
+                }
+            }
+        }
+        self.items.push(item);
+        self.nodes.push(Node { item: index, children: BTreeMap::new() });
+    }
+
+    /// The item closest to `target`, with its distance.
+    pub fn nearest(&self, target: &T) -> Option<(&T, usize)> {
+        self.k_nearest(target, 1).into_iter().next()
+    }
+
+    /// The `k` items closest to `target`, nearest first.
+    pub fn k_nearest(&self, target: &T, k: usize) -> Vec<(&T, usize)> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut best = KBest::new(k);
+        self.search(target, &mut best);
+        resolve(&self.items, best.into_sorted_vec())
+    }
+
+    /// Every item within `max_distance` of `target`, nearest first.
+    pub fn find_within(&self, target: &T, max_distance: usize) -> Vec<(&T, usize)> {
+        let mut found = Within::new(max_distance);
+        self.search(target, &mut found);
+        resolve(&self.items, found.into_sorted_vec())
+    }
+
+    fn search<C: Collector<usize>>(&self, target: &T, found: &mut C) {
+        if self.nodes.is_empty() {
+            return;
+        }
+        // An explicit stack: a million-word tree can be deep.
+        let mut stack = vec![0];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            let d = target.distance(&self.items[node.item]);
+            found.offer(d, node.item);
+
+            let mut children: Vec<(usize, usize)> = match found.bound() {
+                Some(bound) => node.children
+                    .range(d.saturating_sub(bound)..=d.saturating_add(bound))
+                    .map(|(&key, &child)| (key, child))
+                    .collect(),
+                None => node.children.iter().map(|(&key, &child)| (key, child)).collect(),
+            };
+            // Push the most promising children last so they're searched
+            // first, tightening the bound before the rest are looked at.
+            children.sort_by_key(|&(key, _)| std::cmp::Reverse(key.abs_diff(d)));
+            stack.extend(children.into_iter().map(|(_, child)| child));
+        }
+    }
+}
+
+impl<T: MeasureDistance<Distance=usize>> FromIterator<T> for BkTree<T> {
+    fn from_iter<I: IntoIterator<Item=T>>(iter: I) -> BkTree<T> {
+        let mut tree = BkTree::new();
+        for item in iter {
+            tree.insert(item);
+        }
+        tree
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{k_nearest, within_radius, DamerauLevenshtein};
+    use crate::rand::{Rng, XorShiftRng};
+
+    #[test]
+    fn test_fuzzy_lookup() {
+        let words = ["book", "books", "cake", "boo", "cape", "cart", "boon", "cook"];
+        let tree: BkTree<&str> = words.iter().copied().collect();
+        let found: Vec<_> = tree.find_within(&"bool", 1).into_iter().map(|(w, _)| *w).collect();
+        assert_eq!(found, vec!["book", "boo", "boon"]);
+        assert_eq!(tree.nearest(&"caqe"), Some((&"cake", 1)));
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut rng = XorShiftRng::seed_from_u64(31);
+        let mut word = || -> DamerauLevenshtein<String> {
+            let len = 1 + rng.below(7) as usize;
+            DamerauLevenshtein((0..len).map(|_| (b'a' + rng.below(5) as u8) as char).collect())
+        };
+        let words: Vec<_> = (0..400).map(|_| word()).collect();
+        let tree: BkTree<_> = words.iter().cloned().collect();
+        for _ in 0..100 {
+            let target = word();
+            assert_eq!(tree.k_nearest(&target, 3), k_nearest(&target, &words, 3));
+            assert_eq!(tree.find_within(&target, 2), within_radius(&target, &words, 2));
+        }
+    }
+}
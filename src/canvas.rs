@@ -0,0 +1,228 @@
+//! The `Canvas` that `Visible::draw` renders onto: a grid of character
+//! cells, each with its own colours, drawn in layers.
+
+use std::fmt;
+
+/// The eight standard terminal colours, plus the terminal's own default.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Color {
+    #[default]
+    Default,
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+}
+
+/// One character cell.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Cell {
+    pub ch: char,
+    pub fg: Color,
+    pub bg: Color,
+}
+
+impl Cell {
+    pub fn new(ch: char, fg: Color, bg: Color) -> Cell {
+        Cell { ch, fg, bg }
+    }
+}
+
+/// A blank cell.
+impl Default for Cell {
+    fn default() -> Cell {
+        Cell { ch: ' ', fg: Color::Default, bg: Color::Default }
+    }
+}
+
+/// A `width` by `height` grid of cells, with (0, 0) at the top left.
+///
+/// Drawing goes to the current layer, and layers are composited in order,
+/// each covering the ones below wherever it has been drawn on. Anything
+/// drawn outside the grid is clipped.
+#[derive(Clone, Debug)]
+pub struct Canvas {
+    width: i32,
+    height: i32,
+    /// Each layer holds `width * height` cells, row by row; `None` where
+    /// nothing has been drawn.
+    layers: Vec<Vec<Option<Cell>>>,
+    layer: usize,
+    fg: Color,
+    bg: Color,
+}
+
+impl Canvas {
+    /// A blank canvas with one layer. Panics if either size is negative,
+    /// or if there would be more cells than an `i32` can count.
+    pub fn new(width: i32, height: i32) -> Canvas {
+        assert!(width >= 0 && height >= 0, "canvas size can't be negative");
+        let cells = width.checked_mul(height).expect("canvas is too big");
+        Canvas {
+            width,
+            height,
+            layers: vec![vec![None; cells as usize]],
+            layer: 0,
+            fg: Color::Default,
+            bg: Color::Default,
+        }
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        0 <= x && x < self.width && 0 <= y && y < self.height
+    }
+
+    fn offset(&self, x: i32, y: i32) -> Option<usize> {
+        if self.in_bounds(x, y) {
+            Some((y * self.width + x) as usize)
+        } else {
+            None
+        }
+    }
+
+    /// The layer drawing currently goes to.
+    pub fn layer(&self) -> usize {
+        self.layer
+    }
+
+    /// Draw on layer `n` from now on, adding empty layers if need be.
+    pub fn select_layer(&mut self, n: usize) {
+        while self.layers.len() <= n {
+            self.layers.push(vec![None; self.layers[0].len()]);
+        }
+        self.layer = n;
+    }
+
+    pub fn layer_count(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Set the colours used by `write_at` and `write_str`.
+    pub fn set_colors(&mut self, fg: Color, bg: Color) {
+        self.fg = fg;
+        self.bg = bg;
+    }
+
+    /// Set a cell on the current layer. Returns false, drawing nothing, if
+    /// (x, y) is off the canvas.
+    pub fn put(&mut self, x: i32, y: i32, cell: Cell) -> bool {
+        match self.offset(x, y) {
+            Some(i) => {
+                self.layers[self.layer][i] = Some(cell);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Write `ch` at (x, y) in the current colours, if that's on the
+    /// canvas.
+    pub fn write_at(&mut self, x: i32, y: i32, ch: char) -> bool {
+        self.put(x, y, Cell::new(ch, self.fg, self.bg))
+    }
+
+    /// Write `s` rightwards from (x, y), clipping whatever doesn't fit.
+    pub fn write_str(&mut self, x: i32, y: i32, s: &str) {
+        for (i, ch) in s.chars().enumerate() {
+            self.write_at(x + i as i32, y, ch);
+        }
+    }
+
+    /// Erase a cell on the current layer, letting lower layers show.
+    pub fn erase(&mut self, x: i32, y: i32) {
+        if let Some(i) = self.offset(x, y) {
+            self.layers[self.layer][i] = None;
+        }
+    }
+
+    /// Erase everything on the current layer.
+    pub fn clear_layer(&mut self) {
+        for cell in &mut self.layers[self.layer] {
+            *cell = None;
+        }
+    }
+
+    /// Erase every layer.
+    pub fn clear(&mut self) {
+        for layer in &mut self.layers {
+            for cell in layer {
+                *cell = None;
+            }
+        }
+    }
+
+    /// What's visible at (x, y) with every layer composited, or `None` if
+    /// that's off the canvas. An upper cell with the default background
+    /// keeps the background of whatever is under it.
+    pub fn cell(&self, x: i32, y: i32) -> Option<Cell> {
+        let i = self.offset(x, y)?;
+        let mut result = Cell::default();
+        for layer in &self.layers {
+            if let Some(cell) = layer[i] {
+                let bg = if cell.bg == Color::Default { result.bg } else { cell.bg };
+                result = Cell { bg, ..cell };
+            }
+        }
+        Some(result)
+    }
+
+    /// The composited cells of row `y`.
+    pub fn row(&self, y: i32) -> impl Iterator<Item=Cell> + '_ {
+        (0..self.width).map(move |x| self.cell(x, y).unwrap_or_default())
+    }
+}
+
+/// The characters, one line per row, without colours.
+impl fmt::Display for Canvas {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for y in 0..self.height {
+            let line: String = self.row(y).map(|c| c.ch).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layers_and_clipping() {
+        let mut canvas = Canvas::new(5, 2);
+        canvas.set_colors(Color::Default, Color::Blue);
+        canvas.write_str(-1, 0, "~~~~~~~~");
+        canvas.select_layer(2);
+        canvas.set_colors(Color::Red, Color::Default);
+        assert!(canvas.write_at(2, 0, '@'));
+        assert!(!canvas.write_at(5, 1, 'x'));
+        canvas.write_str(3, 1, "hello");
+        assert_eq!(canvas.layer_count(), 3);
+        assert_eq!(canvas.to_string(), "~~@~~\n   he\n");
+        assert_eq!(canvas.cell(2, 0), Some(Cell::new('@', Color::Red, Color::Blue)));
+        assert_eq!(canvas.cell(0, 2), None);
+
+        canvas.erase(2, 0);
+        assert_eq!(canvas.cell(2, 0), Some(Cell::new('~', Color::Default, Color::Blue)));
+        canvas.clear();
+        assert_eq!(canvas.to_string(), "     \n     \n");
+    }
+
+    #[test]
+    #[should_panic(expected = "canvas is too big")]
+    fn test_too_big() {
+        Canvas::new(100_000, 100_000);
+    }
+}
@@ -0,0 +1,557 @@
+//! The `MeasureDistance` trait that `nearest` is generic over, and the
+//! standard metrics.
+
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashMap};
+use std::thread;
+
+/// A type whose values are some distance apart.
+///
+/// Implementations should be metrics: the distance from a value to itself is
+/// zero, distances are symmetric, and the triangle inequality holds. The tree
+/// indexes depend on that last property to prune their searches.
+pub trait MeasureDistance {
+    type Distance: PartialOrd + Copy;
+
+    fn distance(&self, other: &Self) -> Self::Distance;
+}
+
+impl<T: MeasureDistance + ?Sized> MeasureDistance for &T {
+    type Distance = T::Distance;
+
+    fn distance(&self, other: &Self) -> T::Distance {
+        (**self).distance(*other)
+    }
+}
+
+/// Return a ref to the point in `candidates` that's
+/// closest to the `target` point, along with its distance.
+/// Ties go to the earliest candidate, and incomparable distances such as
+/// NaN lose to every other; an empty slice gives `None`.
+pub fn nearest<'t, 'c, P>(target: &'t P, candidates: &'c [P]) -> Option<(&'c P, P::Distance)>
+    where P: MeasureDistance
+{
+    let mut best: Option<(&'c P, P::Distance)> = None;
+    for candidate in candidates {
+        let d = target.distance(candidate);
+        let closer = match best {
+            Some((_, best_d)) => compare_distances(&d, &best_d) == Ordering::Less,
+            None => true,
+        };
+        if closer {
+            best = Some((candidate, d));
+        }
+    }
+    best
+}
+
+/// Order two distances, putting incomparable values such as NaN last so
+/// searches stay deterministic.
+pub(crate) fn compare_distances<D: PartialOrd>(a: &D, b: &D) -> Ordering {
+    a.partial_cmp(b).unwrap_or_else(|| {
+        #[allow(clippy::eq_op)]
+        let (a_nan, b_nan) = (a != a, b != b);
+        a_nan.cmp(&b_nan)
+    })
+}
+
+/// A candidate's distance and position, ordered by distance and then by
+/// position, so equally distant candidates come out in slice order.
+pub(crate) struct Ranked<D> {
+    pub distance: D,
+    pub index: usize,
+}
+
+impl<D: PartialOrd> Ord for Ranked<D> {
+    fn cmp(&self, other: &Ranked<D>) -> Ordering {
+        compare_distances(&self.distance, &other.distance)
+            .then(self.index.cmp(&other.index))
+    }
+}
+
+impl<D: PartialOrd> PartialOrd for Ranked<D> {
+    fn partial_cmp(&self, other: &Ranked<D>) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<D: PartialOrd> PartialEq for Ranked<D> {
+    fn eq(&self, other: &Ranked<D>) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<D: PartialOrd> Eq for Ranked<D> {}
+
+/// Gathers the results of a search as candidates are offered to it. The
+/// index structures use `bound` to skip any part of the space that can't
+/// hold a candidate closer than it.
+pub(crate) trait Collector<D> {
+    fn offer(&mut self, distance: D, index: usize);
+
+    /// The furthest distance still worth offering, if there is a limit yet.
+    fn bound(&self) -> Option<D>;
+}
+
+/// The best `k` candidates offered so far. The index structures share this
+/// with the brute-force searches, so they break ties the same way.
+pub(crate) struct KBest<D> {
+    k: usize,
+    // A max-heap; its top is the one to evict next.
+    heap: BinaryHeap<Ranked<D>>,
+}
+
+impl<D: PartialOrd> KBest<D> {
+    pub fn new(k: usize) -> KBest<D> {
+        KBest { k, heap: BinaryHeap::with_capacity(k + 1) }
+    }
+
+    pub fn into_sorted_vec(self) -> Vec<Ranked<D>> {
+        self.heap.into_sorted_vec()
+    }
+}
+
+impl<D: PartialOrd + Copy> Collector<D> for KBest<D> {
+    fn offer(&mut self, distance: D, index: usize) {
+        let ranked = Ranked { distance, index };
+        if self.heap.len() < self.k {
+            self.heap.push(ranked);
+        } else if self.heap.peek().is_some_and(|worst| ranked < *worst) {
+            self.heap.pop();
+            self.heap.push(ranked);
+        }
+    }
+
+    /// Once full, a candidate must beat (or tie) the worst one kept.
+    fn bound(&self) -> Option<D> {
+        if self.heap.len() < self.k {
+            None
+        } else {
+            self.heap.peek().map(|worst| worst.distance)
+        }
+    }
+}
+
+/// Every candidate offered within `radius`.
+pub(crate) struct Within<D> {
+    radius: D,
+    found: Vec<Ranked<D>>,
+}
+
+impl<D: PartialOrd> Within<D> {
+    pub fn new(radius: D) -> Within<D> {
+        Within { radius, found: vec![] }
+    }
+
+    pub fn into_sorted_vec(mut self) -> Vec<Ranked<D>> {
+        self.found.sort();
+        self.found
+    }
+}
+
+impl<D: PartialOrd + Copy> Collector<D> for Within<D> {
+    fn offer(&mut self, distance: D, index: usize) {
+        if distance <= self.radius {
+            self.found.push(Ranked { distance, index });
+        }
+    }
+
+    fn bound(&self) -> Option<D> {
+        Some(self.radius)
+    }
+}
+
+/// The `k` best of `candidates`, whose first element sits at `offset` in the
+/// caller's slice.
+fn k_best<P>(target: &P, candidates: &[P], offset: usize, k: usize) -> Vec<Ranked<P::Distance>>
+    where P: MeasureDistance
+{
+    let mut best = KBest::new(k);
+    for (i, candidate) in candidates.iter().enumerate() {
+        best.offer(target.distance(candidate), offset + i);
+    }
+    best.into_sorted_vec()
+}
+
+fn within<P>(target: &P, candidates: &[P], offset: usize, radius: P::Distance)
+    -> Vec<Ranked<P::Distance>>
+    where P: MeasureDistance
+{
+    let mut found = Within::new(radius);
+    for (i, candidate) in candidates.iter().enumerate() {
+        found.offer(target.distance(candidate), offset + i);
+    }
+    found.found
+}
+
+/// How lopsided the tree indexes let `insert` make a subtree: neither side
+/// of a node may hold more than this fraction of the points beneath it.
+pub(crate) const BALANCE: f64 = 0.7;
+
+/// Is a new leaf `depth` steps below the root too deep for a tree of `len`
+/// points? If so, some node on the way down is out of `BALANCE`.
+pub(crate) fn too_deep(depth: usize, len: usize) -> bool {
+    depth as f64 > (len as f64).ln() / (1.0 / BALANCE).ln()
+}
+
+/// Where on `path`, the nodes from the root down to a leaf that's
+/// `too_deep`, the lowest node out of `BALANCE` is. `size` gives the number
+/// of points beneath a node, itself included. Rebuilding that node's
+/// subtree keeps the depth of the whole tree logarithmic.
+pub(crate) fn scapegoat<F>(path: &[usize], size: F) -> usize
+    where F: Fn(usize) -> usize
+{
+    (0..path.len() - 1).rev()
+        .find(|&i| size(path[i + 1]) as f64 > BALANCE * size(path[i]) as f64)
+        .unwrap_or(0)
+}
+
+pub(crate) fn resolve<P>(candidates: &[P], ranked: Vec<Ranked<P::Distance>>) -> Vec<(&P, P::Distance)>
+    where P: MeasureDistance
+{
+    ranked.into_iter().map(|r| (&candidates[r.index], r.distance)).collect()
+}
+
+/// The `k` candidates closest to `target`, nearest first, with their
+/// distances. Equally distant candidates are returned in slice order.
+pub fn k_nearest<'c, P>(target: &P, candidates: &'c [P], k: usize)
+    -> Vec<(&'c P, P::Distance)>
+    where P: MeasureDistance
+{
+    resolve(candidates, k_best(target, candidates, 0, k))
+}
+
+/// Every candidate no further than `radius` from `target`, nearest first,
+/// with ties in slice order.
+pub fn within_radius<'c, P>(target: &P, candidates: &'c [P], radius: P::Distance)
+    -> Vec<(&'c P, P::Distance)>
+    where P: MeasureDistance
+{
+    let mut ranked = within(target, candidates, 0, radius);
+    ranked.sort();
+    resolve(candidates, ranked)
+}
+
+/// Below this many candidates, spreading the work across threads costs more
+/// than it saves.
+const PARALLEL_THRESHOLD: usize = 16 * 1024;
+
+/// Split `candidates` into one chunk per available core, run `search` on
+/// each in its own thread, and gather the results.
+fn par_chunks<P, F>(candidates: &[P], search: F) -> Vec<Ranked<P::Distance>>
+    where P: MeasureDistance + Sync,
+          P::Distance: Send,
+          F: Fn(&[P], usize) -> Vec<Ranked<P::Distance>> + Sync
+{
+    let threads = thread::available_parallelism().map_or(1, |n| n.get());
+    if threads == 1 || candidates.len() < PARALLEL_THRESHOLD {
+        return search(candidates, 0);
+    }
+    let chunk_len = candidates.len().div_ceil(threads);
+    let search = &search;
+    thread::scope(|scope| {
+        let handles: Vec<_> = candidates.chunks(chunk_len).enumerate()
+            .map(|(n, chunk)| scope.spawn(move || search(chunk, n * chunk_len)))
+            .collect();
+        handles.into_iter()
+            .flat_map(|h| h.join().expect("search thread panicked"))
+            .collect()
+    })
+}
+
+/// `k_nearest`, with large slices searched on all available cores. The
+/// result is exactly what `k_nearest` would return.
+pub fn par_k_nearest<'c, P>(target: &P, candidates: &'c [P], k: usize)
+    -> Vec<(&'c P, P::Distance)>
+    where P: MeasureDistance + Sync,
+          P::Distance: Send
+{
+    let mut ranked = par_chunks(candidates, |chunk, offset| k_best(target, chunk, offset, k));
+    ranked.sort();
+    ranked.truncate(k);
+    resolve(candidates, ranked)
+}
+
+/// `within_radius`, with large slices searched on all available cores.
+pub fn par_within_radius<'c, P>(target: &P, candidates: &'c [P], radius: P::Distance)
+    -> Vec<(&'c P, P::Distance)>
+    where P: MeasureDistance + Sync,
+          P::Distance: Send + Sync
+{
+    let mut ranked = par_chunks(candidates, |chunk, offset| within(target, chunk, offset, radius));
+    ranked.sort();
+    resolve(candidates, ranked)
+}
+
+/// Straight-line distance between coordinate arrays.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Euclidean<T>(pub T);
+
+/// Distance along the axes, as a taxi drives through a grid of streets.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Manhattan<T>(pub T);
+
+/// The largest difference along any one axis, as a chess king moves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Chebyshev<T>(pub T);
+
+fn axis_differences<'a, N, const D: usize>(a: &'a [N; D], b: &'a [N; D])
+    -> impl Iterator<Item=f64> + 'a
+    where N: Copy + Into<f64>
+{
+    a.iter().zip(b.iter()).map(|(&x, &y)| (x.into() - y.into()).abs())
+}
+
+impl<N, const D: usize> MeasureDistance for Euclidean<[N; D]>
+    where N: Copy + Into<f64>
+{
+    type Distance = f64;
+
+    fn distance(&self, other: &Self) -> f64 {
+        axis_differences(&self.0, &other.0).map(|d| d * d).sum::<f64>().sqrt()
+    }
+}
+
+impl<N, const D: usize> MeasureDistance for Manhattan<[N; D]>
+    where N: Copy + Into<f64>
+{
+    type Distance = f64;
+
+    fn distance(&self, other: &Self) -> f64 {
+        axis_differences(&self.0, &other.0).sum()
+    }
+}
+
+impl<N, const D: usize> MeasureDistance for Chebyshev<[N; D]>
+    where N: Copy + Into<f64>
+{
+    type Distance = f64;
+
+    fn distance(&self, other: &Self) -> f64 {
+        axis_differences(&self.0, &other.0).fold(0.0, f64::max)
+    }
+}
+
+/// Vectors whose length is only known at run time, such as embeddings.
+/// Both must have the same length.
+impl MeasureDistance for Euclidean<Vec<f64>> {
+    type Distance = f64;
+
+    fn distance(&self, other: &Self) -> f64 {
+        assert_eq!(self.0.len(), other.0.len(), "vectors differ in length");
+        self.0.iter().zip(&other.0).map(|(x, y)| (x - y) * (x - y)).sum::<f64>().sqrt()
+    }
+}
+
+/// The angle between two vectors, as a fraction of a half turn: 0 when they
+/// point the same way, 1 when they're opposite. This is the metric form of
+/// cosine distance; `1 - cos θ` itself breaks the triangle inequality.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cosine<T>(pub T);
+
+impl MeasureDistance for Cosine<Vec<f64>> {
+    type Distance = f64;
+
+    fn distance(&self, other: &Self) -> f64 {
+        assert_eq!(self.0.len(), other.0.len(), "vectors differ in length");
+        let dot: f64 = self.0.iter().zip(&other.0).map(|(x, y)| x * y).sum();
+        let norms = self.0.iter().map(|x| x * x).sum::<f64>().sqrt()
+            * other.0.iter().map(|x| x * x).sum::<f64>().sqrt();
+        if norms == 0.0 {
+            // A zero vector has no direction; call it orthogonal to everything.
+            return if self.0 == other.0 { 0.0 } else { 0.5 };
+        }
+        (dot / norms).clamp(-1.0, 1.0).acos() / std::f64::consts::PI
+    }
+}
+
+/// Mean radius of the Earth, in kilometres.
+pub const EARTH_RADIUS_KM: f64 = 6371.0;
+
+/// A point on the Earth's surface, in degrees. Distances between them are
+/// great-circle distances in kilometres, by the haversine formula.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LatLon {
+    pub lat: f64,
+    pub lon: f64,
+}
+
+impl LatLon {
+    pub fn new(lat: f64, lon: f64) -> LatLon {
+        LatLon { lat, lon }
+    }
+}
+
+impl MeasureDistance for LatLon {
+    type Distance = f64;
+
+    fn distance(&self, other: &LatLon) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlon = (other.lon - self.lon).to_radians();
+        let h = (dlat / 2.0).sin().powi(2)
+            + lat1.cos() * lat2.cos() * (dlon / 2.0).sin().powi(2);
+        // Rounding can push `h` a hair past 1 for antipodal points.
+        2.0 * EARTH_RADIUS_KM * h.sqrt().min(1.0).asin()
+    }
+}
+
+/// Byte strings are measured by Hamming distance: the number of positions at
+/// which they differ. When the lengths differ, each extra byte counts as one
+/// more difference, which keeps this a metric.
+impl MeasureDistance for [u8] {
+    type Distance = usize;
+
+    fn distance(&self, other: &[u8]) -> usize {
+        let differing = self.iter().zip(other).filter(|(a, b)| a != b).count();
+        differing + self.len().max(other.len()) - self.len().min(other.len())
+    }
+}
+
+impl MeasureDistance for Vec<u8> {
+    type Distance = usize;
+
+    fn distance(&self, other: &Vec<u8>) -> usize {
+        self[..].distance(&other[..])
+    }
+}
+
+/// Strings are measured by Levenshtein distance: the fewest single-character
+/// insertions, deletions and substitutions turning one into the other.
+impl MeasureDistance for str {
+    type Distance = usize;
+
+    fn distance(&self, other: &str) -> usize {
+        let a: Vec<char> = self.chars().collect();
+        let b: Vec<char> = other.chars().collect();
+
+        // Only the previous row of the edit-distance table is needed.
+        let mut prev: Vec<usize> = (0..=b.len()).collect();
+        let mut row = vec![0; b.len() + 1];
+        for i in 1..=a.len() {
+            row[0] = i;
+            for j in 1..=b.len() {
+                let substitution = prev[j - 1] + (a[i - 1] != b[j - 1]) as usize;
+                row[j] = substitution.min(prev[j] + 1).min(row[j - 1] + 1);
+            }
+            std::mem::swap(&mut prev, &mut row);
+        }
+        prev[b.len()]
+    }
+}
+
+impl MeasureDistance for String {
+    type Distance = usize;
+
+    fn distance(&self, other: &String) -> usize {
+        self.as_str().distance(other.as_str())
+    }
+}
+
+/// Strings measured by Damerau-Levenshtein distance: like Levenshtein, but
+/// swapping two adjacent characters counts as one edit. This is the
+/// unrestricted form, which (unlike optimal string alignment) is a true
+/// metric, so it's safe to use in the tree indexes.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct DamerauLevenshtein<S>(pub S);
+
+impl<S: AsRef<str>> MeasureDistance for DamerauLevenshtein<S> {
+    type Distance = usize;
+
+    fn distance(&self, other: &Self) -> usize {
+        let a: Vec<char> = self.0.as_ref().chars().collect();
+        let b: Vec<char> = other.0.as_ref().chars().collect();
+        let (n, m) = (a.len(), b.len());
+        let max = n + m;
+
+        // Lowrance and Wagner's algorithm. The table has an extra leading row
+        // and column filled with `max`, and `last_row` remembers the last row
+        // in which each character of `a` appeared.
+        let mut last_row: HashMap<char, usize> = HashMap::new();
+        let mut table = vec![vec![0; m + 2]; n + 2];
+        table[0][0] = max;
+        for i in 0..=n {
+            table[i + 1][0] = max;
+            table[i + 1][1] = i;
+        }
+        for j in 0..=m {
+            table[0][j + 1] = max;
+            table[1][j + 1] = j;
+        }
+
+        for i in 1..=n {
+            let mut last_col = 0;
+            for j in 1..=m {
+                let k = *last_row.get(&b[j - 1]).unwrap_or(&0);
+                let l = last_col;
+                let cost = if a[i - 1] == b[j - 1] {
+                    last_col = j;
+                    0
+                } else {
+                    1
+                };
+                table[i + 1][j + 1] = (table[i][j] + cost)
+                    .min(table[i + 1][j] + 1)
+                    .min(table[i][j + 1] + 1)
+                    .min(table[k][l] + (i - k - 1) + 1 + (j - l - 1));
+            }
+            last_row.insert(a[i - 1], i);
+        }
+        table[n + 1][m + 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nearest() {
+        let points = [Euclidean([0, 0]), Euclidean([3, 4]), Euclidean([-1, 1])];
+        assert_eq!(nearest(&Euclidean([2, 3]), &points), Some((&points[1], 2f64.sqrt())));
+        assert_eq!(nearest(&Euclidean([2, 3]), &[]), None);
+
+        let words = ["kitten", "sitting", "mitten"];
+        assert_eq!(nearest(&"sitten", &words), Some((&"kitten", 1)));
+
+        // NaN sorts last, as it does for `k_nearest`.
+        let points = [Euclidean([f64::NAN]), Euclidean([1.0])];
+        let target = Euclidean([0.0]);
+        assert_eq!(nearest(&target, &points), Some((&points[1], 1.0)));
+        assert!(std::ptr::eq(k_nearest(&target, &points, 1)[0].0, &points[1]));
+    }
+
+    #[test]
+    fn test_k_nearest_and_radius() {
+        let points: Vec<_> = [1, 5, 3, 5, -3, 9].iter().map(|&x| Euclidean([x])).collect();
+        let target = Euclidean([4]);
+        let found: Vec<_> = k_nearest(&target, &points, 4).iter()
+            .map(|&(p, d)| (p.0[0], d))
+            .collect();
+        assert_eq!(found, vec![(5, 1.0), (3, 1.0), (5, 1.0), (1, 3.0)]);
+        assert!(std::ptr::eq(k_nearest(&target, &points, 1)[0].0, &points[1]));
+        assert_eq!(within_radius(&target, &points, 1.0).len(), 3);
+
+        let many: Vec<_> = (0..50_000).map(|i| Euclidean([i % 977, i % 13])).collect();
+        let target = Euclidean([500, 6]);
+        assert_eq!(par_k_nearest(&target, &many, 10), k_nearest(&target, &many, 10));
+        assert_eq!(par_within_radius(&target, &many, 5.0), within_radius(&target, &many, 5.0));
+    }
+
+    #[test]
+    fn test_metrics() {
+        assert_eq!(Manhattan([1.0, 2.0]).distance(&Manhattan([4.0, -2.0])), 7.0);
+        assert_eq!(Chebyshev([1.0, 2.0]).distance(&Chebyshev([4.0, -2.0])), 4.0);
+        assert_eq!("kitten".distance("sitting"), 3);
+        let dl = |a, b| DamerauLevenshtein(a).distance(&DamerauLevenshtein(b));
+        assert_eq!(dl("ca", "abc"), 2);
+        assert_eq!(dl("form", "from"), 1);
+        assert_eq!(b"karolin"[..].distance(&b"kathrin"[..]), 3);
+        assert_eq!(b"abc"[..].distance(&b"abcde"[..]), 2);
+
+        // Paris to London is about 344 km.
+        let paris = LatLon::new(48.8566, 2.3522);
+        let london = LatLon::new(51.5074, -0.1278);
+        assert!((paris.distance(&london) - 343.5).abs() < 1.0);
+    }
+}
@@ -0,0 +1,271 @@
+//! Getting a `Canvas` out of the terminal: as a PPM image, as SVG, or as an
+//! asciicast v2 recording of a `World` that `asciinema play` can replay.
+
+use std::io::{self, Write};
+
+use crate::canvas::{Canvas, Cell, Color};
+use crate::terminal::{Mode, Terminal, PLACEHOLDER};
+use crate::world::World;
+
+mod font;
+
+/// Each cell of a PPM image is this many pixels across and down (before
+/// scaling): a glyph plus a pixel of spacing.
+pub const PPM_CELL: (usize, usize) = (font::WIDTH + 1, font::HEIGHT + 1);
+
+/// Each cell of an SVG image is this many units across and down.
+pub const SVG_CELL: (usize, usize) = (8, 16);
+
+/// The xterm RGB values of `color`, standing in `default` for
+/// `Color::Default`.
+fn rgb(color: Color, default: [u8; 3]) -> [u8; 3] {
+    match color {
+        Color::Default => default,
+        Color::Black => [0, 0, 0],
+        Color::Red => [205, 0, 0],
+        Color::Green => [0, 205, 0],
+        Color::Yellow => [205, 205, 0],
+        Color::Blue => [0, 0, 238],
+        Color::Magenta => [205, 0, 205],
+        Color::Cyan => [0, 205, 205],
+        Color::White => [229, 229, 229],
+    }
+}
+
+/// Default colours are light text on black, like most terminals.
+const DEFAULT_FG: [u8; 3] = [229, 229, 229];
+const DEFAULT_BG: [u8; 3] = [0, 0, 0];
+
+/// Write `canvas` as a binary PPM (P6) image, each pixel of the built-in
+/// font blown up to `scale` by `scale` pixels. Panics if `scale` is 0.
+pub fn write_ppm<W: Write + ?Sized>(canvas: &Canvas, scale: usize, out: &mut W) -> io::Result<()> {
+    assert!(scale > 0, "can't scale an image to nothing");
+    let (cell_w, cell_h) = PPM_CELL;
+    let (width, height) = (canvas.width() as usize, canvas.height() as usize);
+    write!(out, "P6\n{} {}\n255\n", width * cell_w * scale, height * cell_h * scale)?;
+
+    let mut line = Vec::with_capacity(width * cell_w * scale * 3);
+    for y in 0..canvas.height() {
+        let cells: Vec<Cell> = canvas.row(y).collect();
+        for py in 0..cell_h {
+            line.clear();
+            for cell in &cells {
+                for px in 0..cell_w {
+                    let color = if font::lit(cell.ch, px, py) {
+                        rgb(cell.fg, DEFAULT_FG)
+                    } else {
+                        rgb(cell.bg, DEFAULT_BG)
+                    };
+                    for _ in 0..scale {
+                        line.extend_from_slice(&color);
+                    }
+                }
+            }
+            for _ in 0..scale {
+                out.write_all(&line)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn hex([r, g, b]: [u8; 3]) -> String {
+    format!("#{:02x}{:02x}{:02x}", r, g, b)
+}
+
+/// Write `text` escaped for XML. Control characters, which XML 1.0 doesn't
+/// allow or would show as spaces, become `PLACEHOLDER`s.
+fn write_escaped<W: Write + ?Sized>(text: &str, out: &mut W) -> io::Result<()> {
+    for ch in text.chars() {
+        match ch {
+            ch if ch.is_control() => write!(out, "{}", PLACEHOLDER)?,
+            '&' => out.write_all(b"&amp;")?,
+            '<' => out.write_all(b"&lt;")?,
+            '>' => out.write_all(b"&gt;")?,
+            '"' => out.write_all(b"&quot;")?,
+            ch => write!(out, "{}", ch)?,
+        }
+    }
+    Ok(())
+}
+
+/// Write `canvas` as an SVG image in a monospace font, with one `<text>`
+/// element for each run of cells in a row that share their colours, and a
+/// `<rect>` behind any run with a background colour.
+pub fn write_svg<W: Write + ?Sized>(canvas: &Canvas, out: &mut W) -> io::Result<()> {
+    let (cell_w, cell_h) = SVG_CELL;
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="monospace" font-size="{}">"#,
+             canvas.width() as usize * cell_w, canvas.height() as usize * cell_h, cell_h * 7 / 8)?;
+    writeln!(out, r#"<rect width="100%" height="100%" fill="{}"/>"#, hex(DEFAULT_BG))?;
+    for y in 0..canvas.height() {
+        let cells: Vec<Cell> = canvas.row(y).collect();
+        let mut start = 0;
+        for run in cells.chunk_by(|a, b| (a.fg, a.bg) == (b.fg, b.bg)) {
+            let (x, top) = (start * cell_w, y as usize * cell_h);
+            let width = run.len() * cell_w;
+            start += run.len();
+            if run[0].bg != Color::Default {
+                writeln!(out, r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}"/>"#,
+                         x, top, width, cell_h, hex(rgb(run[0].bg, DEFAULT_BG)))?;
+            }
+            let text: String = run.iter().map(|c| c.ch).collect();
+            if text.trim().is_empty() {
+                continue;
+            }
+            // Pin the run's width, so it lines up whatever font is used.
+            write!(out, r#"<text x="{}" y="{}" fill="{}" textLength="{}" lengthAdjust="spacingAndGlyphs" xml:space="preserve">"#,
+                   x, top + cell_h * 3 / 4, hex(rgb(run[0].fg, DEFAULT_FG)), width)?;
+            write_escaped(&text, out)?;
+            writeln!(out, "</text>")?;
+        }
+    }
+    writeln!(out, "</svg>")
+}
+
+fn write_json_string<W: Write + ?Sized>(text: &str, out: &mut W) -> io::Result<()> {
+    out.write_all(b"\"")?;
+    for ch in text.chars() {
+        match ch {
+            '"' => out.write_all(b"\\\"")?,
+            '\\' => out.write_all(b"\\\\")?,
+            '\n' => out.write_all(b"\\n")?,
+            ch if (ch as u32) < 0x20 => write!(out, "\\u{:04x}", ch as u32)?,
+            ch => write!(out, "{}", ch)?,
+        }
+    }
+    out.write_all(b"\"")
+}
+
+/// Records frames as an asciicast v2 file: a JSON header line, then one
+/// JSON event per frame holding the ANSI output that draws it. As on a real
+/// terminal, only the cells that changed are sent.
+pub struct Recorder<W: Write> {
+    out: W,
+    terminal: Terminal,
+    width: i32,
+    height: i32,
+    /// The time of the latest frame.
+    time: f64,
+}
+
+impl<W: Write> Recorder<W> {
+    /// Start a recording of a `width` by `height` screen, writing the
+    /// header to `out`.
+    pub fn new(mut out: W, width: i32, height: i32) -> io::Result<Recorder<W>> {
+        writeln!(out, r#"{{"version": 2, "width": {}, "height": {}}}"#, width, height)?;
+        Ok(Recorder { out, terminal: Terminal::new(Mode::Ansi), width, height, time: 0.0 })
+    }
+
+    /// Record `canvas` as the screen at `time` seconds into the recording.
+    /// Frames with no changes are left out. A canvas that isn't the size
+    /// given in the header, or a time that's negative, earlier than the
+    /// last frame's or not finite, is an `InvalidInput` error.
+    pub fn frame(&mut self, time: f64, canvas: &Canvas) -> io::Result<()> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        if (canvas.width(), canvas.height()) != (self.width, self.height) {
+            return Err(invalid(format!("a {}x{} frame in a {}x{} recording",
+                                       canvas.width(), canvas.height(), self.width, self.height)));
+        }
+        if !time.is_finite() || time < self.time {
+            return Err(invalid(format!("a frame at {} seconds, after one at {}", time, self.time)));
+        }
+        self.time = time;
+        let mut bytes = vec![];
+        self.terminal.render(canvas, &mut bytes)?;
+        if bytes.is_empty() {
+            return Ok(());
+        }
+        write!(self.out, "[{:.6}, \"o\", ", time)?;
+        write_json_string(&String::from_utf8_lossy(&bytes), &mut self.out)?;
+        writeln!(self.out, "]")
+    }
+
+    /// Record `world` as it is now, and after each of the next `ticks`
+    /// ticks, timed by the world's clock.
+    pub fn record(&mut self, world: &mut World, ticks: u64) -> io::Result<()> {
+        for tick in 0..=ticks {
+            if tick > 0 {
+                world.tick();
+            }
+            let mut canvas = Canvas::new(self.width, self.height);
+            world.draw(&mut canvas);
+            self.frame(world.time(), &canvas)?;
+        }
+        self.out.flush()
+    }
+
+    /// Finish the recording, giving back the writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::behavior::Walk;
+    use crate::game::{Broom, Direction};
+
+    #[test]
+    fn test_images() {
+        let mut canvas = Canvas::new(2, 1);
+        canvas.set_colors(Color::Red, Color::Blue);
+        canvas.write_str(0, 0, "I<");
+
+        let mut ppm = vec![];
+        write_ppm(&canvas, 2, &mut ppm).unwrap();
+        let header = b"P6\n16 12\n255\n";
+        assert!(ppm.starts_with(header));
+        let pixels = &ppm[header.len()..];
+        assert_eq!(pixels.len(), 16 * 12 * 3);
+        // The top left of 'I' is lit, the spacing after it isn't.
+        assert_eq!(pixels[..3], [205, 0, 0]);
+        assert_eq!(pixels[6 * 3..7 * 3], [0, 0, 238]);
+
+        canvas.set_colors(Color::Default, Color::Default);
+        canvas.write_at(1, 0, '&');
+        let mut svg = vec![];
+        write_svg(&canvas, &mut svg).unwrap();
+        let svg = String::from_utf8(svg).unwrap();
+        assert_eq!(svg.matches("<text").count(), 2);
+        assert_eq!(svg.matches("<rect").count(), 2);
+        assert!(svg.contains(r##"fill="#cd0000" textLength="8" lengthAdjust="spacingAndGlyphs" xml:space="preserve">I</text>"##));
+        assert!(svg.contains(">&amp;</text>"));
+
+        // XML can't hold most control characters at all.
+        canvas.write_at(1, 0, '\x1b');
+        let mut svg = vec![];
+        write_svg(&canvas, &mut svg).unwrap();
+        assert!(String::from_utf8(svg).unwrap().contains(">?</text>"));
+    }
+
+    #[test]
+    fn test_recorder() {
+        let mut world = World::new(4, 1, 0);
+        let broom = world.add_creature(Broom::new(0, 0, 0), 10.0);
+        world.set_behavior(broom, Walk::new(Direction::East, 2));
+        world.add_obstacle(Broom::new(3, 0, 0));
+
+        // Blocked by the wall after two steps, so the last frame is empty.
+        let mut recorder = Recorder::new(vec![], 4, 1).unwrap();
+        recorder.record(&mut world, 3).unwrap();
+        let cast = String::from_utf8(recorder.into_inner()).unwrap();
+        let lines: Vec<&str> = cast.lines().collect();
+        assert_eq!(lines, [
+            r#"{"version": 2, "width": 4, "height": 1}"#,
+            r#"[0.000000, "o", "\u001b[0m\u001b[2J\u001b[1;1HM  M"]"#,
+            r#"[0.100000, "o", "\u001b[1;1H M"]"#,
+            r#"[0.200000, "o", "\u001b[1;2H M"]"#,
+        ]);
+
+        let mut recorder = Recorder::new(vec![], 4, 1).unwrap();
+        let err = recorder.frame(0.0, &Canvas::new(4, 2)).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        recorder.frame(1.0, &Canvas::new(4, 1)).unwrap();
+        for time in [0.5, f64::NAN, f64::INFINITY] {
+            let err = recorder.frame(time, &Canvas::new(4, 1)).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+        assert_eq!(recorder.into_inner().iter().filter(|&&b| b == b'\n').count(), 2);
+    }
+}
@@ -0,0 +1,121 @@
+//! A tiny 3 by 5 pixel bitmap font, enough to make a `Canvas` legible as an
+//! image.
+
+pub const WIDTH: usize = 3;
+pub const HEIGHT: usize = 5;
+
+/// The glyphs for ' ' to '~', a row of three bits each from top to bottom,
+/// the leftmost pixel in the highest bit.
+const GLYPHS: [[u8; HEIGHT]; 95] = [
+    [0b000, 0b000, 0b000, 0b000, 0b000], // ' '
+    [0b010, 0b010, 0b010, 0b000, 0b010], // !
+    [0b101, 0b101, 0b000, 0b000, 0b000], // "
+    [0b101, 0b111, 0b101, 0b111, 0b101], // #
+    [0b011, 0b110, 0b010, 0b011, 0b110], // $
+    [0b101, 0b001, 0b010, 0b100, 0b101], // %
+    [0b010, 0b101, 0b010, 0b101, 0b011], // &
+    [0b010, 0b010, 0b000, 0b000, 0b000], // '
+    [0b001, 0b010, 0b010, 0b010, 0b001], // (
+    [0b100, 0b010, 0b010, 0b010, 0b100], // )
+    [0b000, 0b101, 0b010, 0b101, 0b000], // *
+    [0b000, 0b010, 0b111, 0b010, 0b000], // +
+    [0b000, 0b000, 0b000, 0b010, 0b100], // ,
+    [0b000, 0b000, 0b111, 0b000, 0b000], // -
+    [0b000, 0b000, 0b000, 0b000, 0b010], // .
+    [0b001, 0b001, 0b010, 0b100, 0b100], // /
+    [0b111, 0b101, 0b101, 0b101, 0b111], // 0
+    [0b010, 0b110, 0b010, 0b010, 0b111], // 1
+    [0b111, 0b001, 0b111, 0b100, 0b111], // 2
+    [0b111, 0b001, 0b011, 0b001, 0b111], // 3
+    [0b101, 0b101, 0b111, 0b001, 0b001], // 4
+    [0b111, 0b100, 0b111, 0b001, 0b111], // 5
+    [0b111, 0b100, 0b111, 0b101, 0b111], // 6
+    [0b111, 0b001, 0b001, 0b010, 0b010], // 7
+    [0b111, 0b101, 0b111, 0b101, 0b111], // 8
+    [0b111, 0b101, 0b111, 0b001, 0b111], // 9
+    [0b000, 0b010, 0b000, 0b010, 0b000], // :
+    [0b000, 0b010, 0b000, 0b010, 0b100], // ;
+    [0b001, 0b010, 0b100, 0b010, 0b001], // <
+    [0b000, 0b111, 0b000, 0b111, 0b000], // =
+    [0b100, 0b010, 0b001, 0b010, 0b100], // >
+    [0b111, 0b001, 0b010, 0b000, 0b010], // ?
+    [0b111, 0b101, 0b111, 0b100, 0b011], // @
+    [0b010, 0b101, 0b111, 0b101, 0b101], // A
+    [0b110, 0b101, 0b110, 0b101, 0b110], // B
+    [0b011, 0b100, 0b100, 0b100, 0b011], // C
+    [0b110, 0b101, 0b101, 0b101, 0b110], // D
+    [0b111, 0b100, 0b110, 0b100, 0b111], // E
+    [0b111, 0b100, 0b110, 0b100, 0b100], // F
+    [0b011, 0b100, 0b101, 0b101, 0b011], // G
+    [0b101, 0b101, 0b111, 0b101, 0b101], // H
+    [0b111, 0b010, 0b010, 0b010, 0b111], // I
+    [0b001, 0b001, 0b001, 0b101, 0b010], // J
+    [0b101, 0b101, 0b110, 0b101, 0b101], // K
+    [0b100, 0b100, 0b100, 0b100, 0b111], // L
+    [0b101, 0b111, 0b111, 0b101, 0b101], // M
+    [0b110, 0b101, 0b101, 0b101, 0b101], // N
+    [0b010, 0b101, 0b101, 0b101, 0b010], // O
+    [0b110, 0b101, 0b110, 0b100, 0b100], // P
+    [0b010, 0b101, 0b101, 0b111, 0b011], // Q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // R
+    [0b011, 0b100, 0b010, 0b001, 0b110], // S
+    [0b111, 0b010, 0b010, 0b010, 0b010], // T
+    [0b101, 0b101, 0b101, 0b101, 0b111], // U
+    [0b101, 0b101, 0b101, 0b101, 0b010], // V
+    [0b101, 0b101, 0b111, 0b111, 0b101], // W
+    [0b101, 0b101, 0b010, 0b101, 0b101], // X
+    [0b101, 0b101, 0b010, 0b010, 0b010], // Y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // Z
+    [0b110, 0b100, 0b100, 0b100, 0b110], // [
+    [0b100, 0b100, 0b010, 0b001, 0b001], // \
+    [0b011, 0b001, 0b001, 0b001, 0b011], // ]
+    [0b010, 0b101, 0b000, 0b000, 0b000], // ^
+    [0b000, 0b000, 0b000, 0b000, 0b111], // _
+    [0b100, 0b010, 0b000, 0b000, 0b000], // `
+    [0b010, 0b101, 0b111, 0b101, 0b101], // a
+    [0b110, 0b101, 0b110, 0b101, 0b110], // b
+    [0b011, 0b100, 0b100, 0b100, 0b011], // c
+    [0b110, 0b101, 0b101, 0b101, 0b110], // d
+    [0b111, 0b100, 0b110, 0b100, 0b111], // e
+    [0b111, 0b100, 0b110, 0b100, 0b100], // f
+    [0b011, 0b100, 0b101, 0b101, 0b011], // g
+    [0b101, 0b101, 0b111, 0b101, 0b101], // h
+    [0b111, 0b010, 0b010, 0b010, 0b111], // i
+    [0b001, 0b001, 0b001, 0b101, 0b010], // j
+    [0b101, 0b101, 0b110, 0b101, 0b101], // k
+    [0b100, 0b100, 0b100, 0b100, 0b111], // l
+    [0b101, 0b111, 0b111, 0b101, 0b101], // m
+    [0b110, 0b101, 0b101, 0b101, 0b101], // n
+    [0b010, 0b101, 0b101, 0b101, 0b010], // o
+    [0b110, 0b101, 0b110, 0b100, 0b100], // p
+    [0b010, 0b101, 0b101, 0b111, 0b011], // q
+    [0b110, 0b101, 0b110, 0b101, 0b101], // r
+    [0b011, 0b100, 0b010, 0b001, 0b110], // s
+    [0b111, 0b010, 0b010, 0b010, 0b010], // t
+    [0b101, 0b101, 0b101, 0b101, 0b111], // u
+    [0b101, 0b101, 0b101, 0b101, 0b010], // v
+    [0b101, 0b101, 0b111, 0b111, 0b101], // w
+    [0b101, 0b101, 0b010, 0b101, 0b101], // x
+    [0b101, 0b101, 0b010, 0b010, 0b010], // y
+    [0b111, 0b001, 0b010, 0b100, 0b111], // z
+    [0b011, 0b010, 0b110, 0b010, 0b011], // {
+    [0b010, 0b010, 0b010, 0b010, 0b010], // |
+    [0b110, 0b010, 0b011, 0b010, 0b110], // }
+    [0b000, 0b011, 0b110, 0b000, 0b000], // ~
+];
+
+/// Anything the font doesn't cover is drawn as a solid block.
+const MISSING: [u8; HEIGHT] = [0b111; HEIGHT];
+
+/// The rows of `ch`'s glyph. Lower case letters look like capitals.
+pub fn glyph(ch: char) -> [u8; HEIGHT] {
+    match ch {
+        ' ' ..= '~' => GLYPHS[ch as usize - ' ' as usize],
+        _ => MISSING,
+    }
+}
+
+/// Is pixel (x, y) of `ch`'s glyph lit?
+pub fn lit(ch: char, x: usize, y: usize) -> bool {
+    x < WIDTH && y < HEIGHT && glyph(ch)[y] & (1 << (WIDTH - 1 - x)) != 0
+}
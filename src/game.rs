@@ -0,0 +1,206 @@
+//! The game world from "Defining and Implementing Traits": things that are
+//! `Visible` on a `Canvas`, starting with the `Broom`.
+
+use std::ops::Range;
+
+use crate::behavior::Sweep;
+use crate::canvas::Canvas;
+use crate::geometry::{Point, Rect};
+
+/// A trait for characters, items, and scenery -
+/// anything in the game world that's visible on screen
+pub trait Visible {
+    /// Render this object on the given canvas.
+    fn draw(&self, canvas: &mut Canvas);
+
+    /// The cells this object may draw on. Nothing is drawn outside them.
+    fn bounds(&self) -> Rect;
+
+    /// Return true if clicking at (x, y) should
+    /// select this object
+    ///
+    /// By default, anywhere within `bounds()` counts; objects with holes or
+    /// odd shapes can be more precise.
+    fn hit_test(&self, x: i32, y: i32) -> bool {
+        self.bounds().contains(&Point::xy(x, y))
+    }
+}
+
+/// One of the eight compass directions. North is up the screen, towards
+/// smaller y.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Direction {
+    North,
+    NorthEast,
+    East,
+    SouthEast,
+    South,
+    SouthWest,
+    West,
+    NorthWest,
+}
+
+impl Direction {
+    /// Clockwise from north.
+    pub const ALL: [Direction; 8] = [
+        Direction::North, Direction::NorthEast, Direction::East, Direction::SouthEast,
+        Direction::South, Direction::SouthWest, Direction::West, Direction::NorthWest,
+    ];
+
+    /// North, east, south and west.
+    pub const CARDINAL: [Direction; 4] =
+        [Direction::North, Direction::East, Direction::South, Direction::West];
+
+    /// The change in (x, y) from taking one step this way.
+    pub fn delta(self) -> (i32, i32) {
+        match self {
+            Direction::North => (0, -1),
+            Direction::NorthEast => (1, -1),
+            Direction::East => (1, 0),
+            Direction::SouthEast => (1, 1),
+            Direction::South => (0, 1),
+            Direction::SouthWest => (-1, 1),
+            Direction::West => (-1, 0),
+            Direction::NorthWest => (-1, -1),
+        }
+    }
+
+    /// The direction of a single step of (dx, dy), if it is one.
+    pub fn from_delta(dx: i32, dy: i32) -> Option<Direction> {
+        Direction::ALL.iter().copied().find(|d| d.delta() == (dx, dy))
+    }
+
+    pub fn is_diagonal(self) -> bool {
+        let (dx, dy) = self.delta();
+        dx != 0 && dy != 0
+    }
+
+    /// Turn clockwise by `eighths` eighths of a full turn; negative turns
+    /// anticlockwise.
+    pub fn turn(self, eighths: i32) -> Direction {
+        Direction::ALL[(self as i32 + eighths).rem_euclid(8) as usize]
+    }
+
+    pub fn opposite(self) -> Direction {
+        self.turn(4)
+    }
+}
+
+/// Someone in the game world, either the player or some other
+/// pixie, gargoyle, squirrel, ogre, etc.
+pub trait Creature: Visible {
+    fn position(&self) -> (i32, i32);
+    fn facing(&self) -> Direction;
+
+    /// Put this creature at (x, y). Its bounds should follow.
+    fn move_to(&mut self, x: i32, y: i32);
+
+    fn turn_to(&mut self, facing: Direction);
+}
+
+/// A broom standing on its bristles at (x, y), its stick rising
+/// `height + 1` cells above them: even a broom of height 0 has a handle.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Broom {
+    pub x: i32,
+    pub y: i32,
+    pub height: i32,
+    pub facing: Direction,
+}
+
+impl Broom {
+    /// A broom facing east.
+    pub fn new(x: i32, y: i32, height: i32) -> Broom {
+        Broom { x, y, height, facing: Direction::East }
+    }
+
+    /// What brooms do: sweep along their row, `span` cells and back.
+    pub fn sweeping_patrol(span: i32) -> Sweep {
+        Sweep::new(span)
+    }
+
+    /// The cells of the stick, above the bristles. Used by `draw`, `bounds`
+    /// and `hit_test` alike so they can't disagree.
+    fn broomstick_range(&self) -> Range<i32> {
+        self.y - self.height - 1 .. self.y
+    }
+}
+
+impl Visible for Broom {
+    fn draw(&self, canvas: &mut Canvas) {
+        for y in self.broomstick_range() {
+            canvas.write_at(self.x, y, '|');
+        }
+        canvas.write_at(self.x, self.y, 'M');
+    }
+
+    fn bounds(&self) -> Rect {
+        Rect::new(Point::xy(self.x, self.broomstick_range().start), Point::xy(self.x, self.y))
+    }
+
+    fn hit_test(&self, x: i32, y: i32) -> bool {
+        self.x == x && (self.broomstick_range().contains(&y) || y == self.y)
+    }
+}
+
+impl Creature for Broom {
+    fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn facing(&self) -> Direction {
+        self.facing
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    fn turn_to(&mut self, facing: Direction) {
+        self.facing = facing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_broom_draw() {
+        let mut canvas = Canvas::new(3, 4);
+        Broom::new(1, 3, 2).draw(&mut canvas);
+        assert_eq!(canvas.to_string(), " | \n | \n | \n M \n");
+
+        // Partly off the top of the canvas.
+        let mut canvas = Canvas::new(3, 2);
+        Broom::new(0, 1, 3).draw(&mut canvas);
+        assert_eq!(canvas.to_string(), "|  \nM  \n");
+    }
+
+    #[test]
+    fn test_broom_hit_test() {
+        let broom = Broom::new(5, 10, 3);
+        assert_eq!(broom.bounds(), Rect::new(Point::xy(5, 6), Point::xy(5, 10)));
+
+        // The tip of the stick, the bristles, and just beyond each.
+        assert!(broom.hit_test(5, 6));
+        assert!(!broom.hit_test(5, 5));
+        assert!(broom.hit_test(5, 10));
+        assert!(!broom.hit_test(5, 11));
+        // Either side of the stick.
+        assert!(!broom.hit_test(4, 8));
+        assert!(!broom.hit_test(6, 8));
+
+        // Every cell drawn is a hit, and nothing else is.
+        let mut canvas = Canvas::new(12, 12);
+        broom.draw(&mut canvas);
+        for y in 0..12 {
+            for x in 0..12 {
+                let drawn = canvas.cell(x, y).unwrap().ch != ' ';
+                assert_eq!(broom.hit_test(x, y), drawn, "at ({}, {})", x, y);
+                assert_eq!(broom.bounds().contains(&Point::xy(x, y)), drawn);
+            }
+        }
+    }
+}
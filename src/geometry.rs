@@ -0,0 +1,292 @@
+//! Points, vectors and bounding boxes in any number of dimensions: the
+//! `Point2d` and `Point3d` that `nearest` was written for.
+
+use std::ops::{Add, Index, Mul, Sub};
+
+use crate::distance::MeasureDistance;
+use crate::kdtree::KdPoint;
+
+/// The dot product, exactly as worked out in "Reverse-Engineering Bounds".
+pub fn dot<N>(v1: &[N], v2: &[N]) -> N
+    where N: Add<Output=N> + Mul<Output=N> + Default + Copy
+{
+    let mut total = N::default();
+    for i in 0 .. v1.len() {
+        total = total + v1[i] * v2[i];
+    }
+    total
+}
+
+/// A point, or equally a vector, with `D` coordinates of type `N`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Point<N, const D: usize> {
+    pub coords: [N; D],
+}
+
+pub type Point2d<N> = Point<N, 2>;
+pub type Point3d<N> = Point<N, 3>;
+
+impl<N, const D: usize> Point<N, D> {
+    pub fn new(coords: [N; D]) -> Point<N, D> {
+        Point { coords }
+    }
+}
+
+impl<N: Copy> Point<N, 2> {
+    pub fn xy(x: N, y: N) -> Point2d<N> {
+        Point { coords: [x, y] }
+    }
+
+    pub fn x(&self) -> N {
+        self.coords[0]
+    }
+
+    pub fn y(&self) -> N {
+        self.coords[1]
+    }
+}
+
+impl<N: Copy> Point<N, 3> {
+    pub fn xyz(x: N, y: N, z: N) -> Point3d<N> {
+        Point { coords: [x, y, z] }
+    }
+
+    pub fn x(&self) -> N {
+        self.coords[0]
+    }
+
+    pub fn y(&self) -> N {
+        self.coords[1]
+    }
+
+    pub fn z(&self) -> N {
+        self.coords[2]
+    }
+}
+
+/// The origin.
+impl<N: Default + Copy, const D: usize> Default for Point<N, D> {
+    fn default() -> Point<N, D> {
+        Point { coords: [N::default(); D] }
+    }
+}
+
+impl<N, const D: usize> From<[N; D]> for Point<N, D> {
+    fn from(coords: [N; D]) -> Point<N, D> {
+        Point { coords }
+    }
+}
+
+impl<N, const D: usize> Index<usize> for Point<N, D> {
+    type Output = N;
+
+    fn index(&self, axis: usize) -> &N {
+        &self.coords[axis]
+    }
+}
+
+impl<N, const D: usize> Add for Point<N, D>
+    where N: Add<Output=N> + Copy
+{
+    type Output = Point<N, D>;
+
+    fn add(self, rhs: Point<N, D>) -> Point<N, D> {
+        let mut coords = self.coords;
+        for (c, r) in coords.iter_mut().zip(rhs.coords.iter()) {
+            *c = *c + *r;
+        }
+        Point { coords }
+    }
+}
+
+impl<N, const D: usize> Sub for Point<N, D>
+    where N: Sub<Output=N> + Copy
+{
+    type Output = Point<N, D>;
+
+    fn sub(self, rhs: Point<N, D>) -> Point<N, D> {
+        let mut coords = self.coords;
+        for (c, r) in coords.iter_mut().zip(rhs.coords.iter()) {
+            *c = *c - *r;
+        }
+        Point { coords }
+    }
+}
+
+/// Scaling by a scalar: `Mul<N>` rather than the default `Mul<Self>`.
+impl<N, const D: usize> Mul<N> for Point<N, D>
+    where N: Mul<Output=N> + Copy
+{
+    type Output = Point<N, D>;
+
+    fn mul(self, rhs: N) -> Point<N, D> {
+        Point { coords: self.coords.map(|c| c * rhs) }
+    }
+}
+
+impl<N, const D: usize> Point<N, D>
+    where N: Add<Output=N> + Mul<Output=N> + Default + Copy
+{
+    pub fn dot(&self, other: &Point<N, D>) -> N {
+        dot(&self.coords, &other.coords)
+    }
+}
+
+impl<N> Point<N, 2>
+    where N: Sub<Output=N> + Mul<Output=N> + Copy
+{
+    /// The z component of the 3-d cross product: positive when `other` is
+    /// anticlockwise from `self`.
+    pub fn cross(&self, other: &Point2d<N>) -> N {
+        self.x() * other.y() - self.y() * other.x()
+    }
+}
+
+impl<N> Point<N, 3>
+    where N: Sub<Output=N> + Mul<Output=N> + Copy
+{
+    pub fn cross(&self, other: &Point3d<N>) -> Point3d<N> {
+        let (a, b) = (self, other);
+        Point::xyz(a.y() * b.z() - a.z() * b.y(),
+                   a.z() * b.x() - a.x() * b.z(),
+                   a.x() * b.y() - a.y() * b.x())
+    }
+}
+
+/// Points are measured by straight-line distance.
+impl<N, const D: usize> MeasureDistance for Point<N, D>
+    where N: Copy + Into<f64>
+{
+    type Distance = f64;
+
+    fn distance(&self, other: &Point<N, D>) -> f64 {
+        self.coords.iter().zip(other.coords.iter())
+            .map(|(&a, &b)| {
+                let d = a.into() - b.into();
+                d * d
+            })
+            .sum::<f64>()
+            .sqrt()
+    }
+}
+
+impl<N, const D: usize> KdPoint for Point<N, D>
+    where N: Copy + Into<f64>
+{
+    fn dimensions(&self) -> usize {
+        D
+    }
+
+    fn coordinate(&self, axis: usize) -> f64 {
+        self.coords[axis].into()
+    }
+}
+
+/// An axis-aligned box, including both corners. On integer grids, such as a
+/// canvas's cells, `max` is the last cell inside the box, not one past it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BoundingBox<N, const D: usize> {
+    pub min: Point<N, D>,
+    pub max: Point<N, D>,
+}
+
+/// A rectangle of canvas cells.
+pub type Rect = BoundingBox<i32, 2>;
+
+impl<N: PartialOrd + Copy, const D: usize> BoundingBox<N, D> {
+    /// The box with corners `a` and `b`, in either order.
+    pub fn new(a: Point<N, D>, b: Point<N, D>) -> BoundingBox<N, D> {
+        let mut min = a;
+        let mut max = a;
+        for axis in 0..D {
+            if b[axis] < min[axis] {
+                min.coords[axis] = b[axis];
+            } else {
+                max.coords[axis] = b[axis];
+            }
+        }
+        BoundingBox { min, max }
+    }
+
+    /// The smallest box containing all of `points`, or `None` if there are
+    /// none.
+    pub fn around<'a, I>(points: I) -> Option<BoundingBox<N, D>>
+        where I: IntoIterator<Item=&'a Point<N, D>>, N: 'a
+    {
+        let mut points = points.into_iter();
+        let first = *points.next()?;
+        Some(points.fold(BoundingBox { min: first, max: first }, |b, p| {
+            b.union(&BoundingBox { min: *p, max: *p })
+        }))
+    }
+
+    pub fn contains(&self, p: &Point<N, D>) -> bool {
+        (0..D).all(|axis| self.min[axis] <= p[axis] && p[axis] <= self.max[axis])
+    }
+
+    pub fn intersects(&self, other: &BoundingBox<N, D>) -> bool {
+        (0..D).all(|axis| self.min[axis] <= other.max[axis] && other.min[axis] <= self.max[axis])
+    }
+
+    /// The overlap of two boxes, if they have one.
+    pub fn intersection(&self, other: &BoundingBox<N, D>) -> Option<BoundingBox<N, D>> {
+        if !self.intersects(other) {
+            return None;
+        }
+        let mut result = *self;
+        for axis in 0..D {
+            if other.min[axis] > result.min[axis] {
+                result.min.coords[axis] = other.min[axis];
+            }
+            if other.max[axis] < result.max[axis] {
+                result.max.coords[axis] = other.max[axis];
+            }
+        }
+        Some(result)
+    }
+
+    /// The smallest box containing both.
+    pub fn union(&self, other: &BoundingBox<N, D>) -> BoundingBox<N, D> {
+        let mut result = *self;
+        for axis in 0..D {
+            if other.min[axis] < result.min[axis] {
+                result.min.coords[axis] = other.min[axis];
+            }
+            if other.max[axis] > result.max[axis] {
+                result.max.coords[axis] = other.max[axis];
+            }
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::nearest;
+
+    #[test]
+    fn test_dot() {
+        assert_eq!(dot(&[1,2,3,4], &[1,1,1,1]), 10);
+        assert_eq!(dot(&[53.0, 7.0], &[1.0, 5.0]), 88.0);
+    }
+
+    #[test]
+    fn test_point_arithmetic() {
+        let a = Point::xyz(1, 0, 0);
+        let b = Point::xyz(0, 1, 0);
+        assert_eq!(a + b * 3 - a, Point::xyz(0, 3, 0));
+        assert_eq!(a.cross(&b), Point::xyz(0, 0, 1));
+        assert_eq!(Point::xy(2, 3).dot(&Point::xy(4, 5)), 23);
+        assert_eq!(Point::xy(1, 0).cross(&Point::xy(0, 1)), 1);
+
+        let points = [Point::xy(0, 0), Point::xy(5, 5), Point::xy(2, 1)];
+        assert_eq!(nearest(&Point::xy(3, 2), &points), Some((&points[2], 2f64.sqrt())));
+
+        let bounds = BoundingBox::around(&points).unwrap();
+        assert_eq!(bounds, BoundingBox::new(Point::xy(5, 5), Point::xy(0, 0)));
+        assert!(bounds.contains(&Point::xy(5, 0)));
+        assert_eq!(bounds.intersection(&BoundingBox::new(Point::xy(4, 4), Point::xy(9, 9))),
+                   Some(BoundingBox::new(Point::xy(4, 4), Point::xy(5, 5))));
+    }
+}
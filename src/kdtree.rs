@@ -0,0 +1,322 @@
+//! A k-d tree over coordinate points, for answering many `nearest`-style
+//! queries against the same set of points.
+
+use crate::distance::{compare_distances, resolve, scapegoat, too_deep, Chebyshev, Collector,
+                      Euclidean, KBest, Manhattan, MeasureDistance, Within};
+
+/// A point with coordinates the k-d tree can split on. It needs at least
+/// one dimension.
+///
+/// The distance between two points must be at least the difference between
+/// their coordinates along any single axis. Every Lp metric (Euclidean,
+/// Manhattan, Chebyshev) satisfies this; the tree relies on it to skip
+/// subtrees.
+pub trait KdPoint: MeasureDistance<Distance=f64> {
+    fn dimensions(&self) -> usize;
+    fn coordinate(&self, axis: usize) -> f64;
+}
+
+macro_rules! kd_point_for_arrays {
+    ($($metric:ident),*) => {
+        $(
+            impl<N, const D: usize> KdPoint for $metric<[N; D]>
+                where N: Copy + Into<f64>
+            {
+                fn dimensions(&self) -> usize {
+                    D
+                }
+
+                fn coordinate(&self, axis: usize) -> f64 {
+                    self.0[axis].into()
+                }
+            }
+        )*
+    }
+}
+
+kd_point_for_arrays!(Euclidean, Manhattan, Chebyshev);
+
+#[derive(Clone, Debug)]
+struct Node {
+    /// Index into `KdTree::points`.
+    point: usize,
+    axis: usize,
+    left: Option<usize>,
+    right: Option<usize>,
+    /// How many points are in this subtree, this one included.
+    size: usize,
+}
+
+/// A k-d tree. Each node splits the points beneath it on one axis: those
+/// below the node's coordinate on the left, those above on the right, and
+/// those level with it on either side.
+///
+/// `bulk_load` builds a balanced tree. `insert` adds leaves, and rebuilds
+/// any subtree it leaves too lopsided, so the tree stays logarithmically
+/// deep whatever order the points come in, duplicates and all.
+///
+/// Queries give exactly the answers the brute-force searches in `distance`
+/// would give on the points in insertion order, ties included.
+#[derive(Clone, Debug)]
+pub struct KdTree<P> {
+    points: Vec<P>,
+    nodes: Vec<Node>,
+    root: Option<usize>,
+}
+
+impl<P: KdPoint> Default for KdTree<P> {
+    fn default() -> Self {
+        KdTree::new()
+    }
+}
+
+impl<P: KdPoint> KdTree<P> {
+    pub fn new() -> KdTree<P> {
+        KdTree { points: vec![], nodes: vec![], root: None }
+    }
+
+    /// Build a balanced tree over `points`, splitting each level at the
+    /// median and cycling through the axes. Panics if the points have no
+    /// dimensions.
+    pub fn bulk_load(points: Vec<P>) -> KdTree<P> {
+        if let Some(p) = points.first() {
+            assert!(p.dimensions() > 0, "k-d tree points need at least one dimension");
+        }
+        let mut tree = KdTree { points, nodes: vec![], root: None };
+        let mut order: Vec<usize> = (0..tree.points.len()).collect();
+        tree.root = tree.build(&mut order, 0, &mut vec![]);
+        tree
+    }
+
+    /// Build a balanced subtree over the points in `order`, `depth` levels
+    /// down, reusing the nodes in `slots` before adding new ones.
+    fn build(&mut self, order: &mut [usize], depth: usize, slots: &mut Vec<usize>) -> Option<usize> {
+        if order.is_empty() {
+            return None;
+        }
+        let size = order.len();
+        let axis = depth % self.points[order[0]].dimensions();
+        let points = &self.points;
+        order.sort_by(|&a, &b| {
+            compare_distances(&points[a].coordinate(axis), &points[b].coordinate(axis))
+                .then(a.cmp(&b))
+        });
+        let mid = order.len() / 2;
+        let point = order[mid];
+        let (below, rest) = order.split_at_mut(mid);
+        let left = self.build(below, depth + 1, slots);
+        let right = self.build(&mut rest[1..], depth + 1, slots);
+        let node = Node { point, axis, left, right, size };
+        match slots.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                Some(slot)
+            }
+            None => {
+                self.nodes.push(node);
+                Some(self.nodes.len() - 1)
+            }
+        }
+    }
+
+    /// Rebuild the subtree at `path[at]`, where `path` runs down from the
+    /// root.
+    fn rebuild(&mut self, path: &[usize], at: usize) {
+        let mut slots = vec![];
+        let mut order = vec![];
+        let mut stack = vec![path[at]];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            slots.push(n);
+            order.push(node.point);
+            stack.extend(node.left.into_iter().chain(node.right));
+        }
+        order.sort_unstable();
+        let new = self.build(&mut order, at, &mut slots);
+        match at.checked_sub(1) {
+            None => self.root = new,
+            Some(i) => {
+                let parent = &mut self.nodes[path[i]];
+                if parent.left == Some(path[at]) {
+                    parent.left = new;
+                } else {
+                    parent.right = new;
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The points in insertion order.
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    /// Add `point`. Panics if it has no dimensions.
+    pub fn insert(&mut self, point: P) {
+        let dims = point.dimensions();
+        assert!(dims > 0, "k-d tree points need at least one dimension");
+        let mut link = self.root;
+        let mut path = vec![];
+        let mut go_left = false;
+        while let Some(n) = link {
+            self.nodes[n].size += 1;
+            let node = &self.nodes[n];
+            go_left = point.coordinate(node.axis)
+                < self.points[node.point].coordinate(node.axis);
+            path.push(n);
+            link = if go_left { node.left } else { node.right };
+        }
+
+        let depth = path.len();
+        self.points.push(point);
+        self.nodes.push(Node {
+            point: self.points.len() - 1,
+            axis: depth % dims,
+            left: None,
+            right: None,
+            size: 1,
+        });
+        let new = self.nodes.len() - 1;
+        match path.last() {
+            None => self.root = Some(new),
+            Some(&p) if go_left => self.nodes[p].left = Some(new),
+            Some(&p) => self.nodes[p].right = Some(new),
+        }
+        path.push(new);
+
+        if too_deep(depth, self.points.len()) {
+            let at = scapegoat(&path, |n| self.nodes[n].size);
+            self.rebuild(&path, at);
+        }
+    }
+
+    /// The closest point to `target`, with its distance.
+    pub fn nearest(&self, target: &P) -> Option<(&P, f64)> {
+        self.k_nearest(target, 1).into_iter().next()
+    }
+
+    /// The `k` closest points to `target`, nearest first.
+    pub fn k_nearest(&self, target: &P, k: usize) -> Vec<(&P, f64)> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut best = KBest::new(k);
+        self.search(self.root, target, &mut best);
+        resolve(&self.points, best.into_sorted_vec())
+    }
+
+    /// Every point within `radius` of `target`, nearest first.
+    pub fn within_radius(&self, target: &P, radius: f64) -> Vec<(&P, f64)> {
+        let mut found = Within::new(radius);
+        self.search(self.root, target, &mut found);
+        resolve(&self.points, found.into_sorted_vec())
+    }
+
+    fn search<C: Collector<f64>>(&self, link: Option<usize>, target: &P, found: &mut C) {
+        let node = match link {
+            Some(n) => &self.nodes[n],
+            None => return,
+        };
+        let point = &self.points[node.point];
+        found.offer(target.distance(point), node.point);
+
+        // Search the side the target is on first; the other side is only
+        // worth a look if the splitting plane is within the current bound.
+        let offset = target.coordinate(node.axis) - point.coordinate(node.axis);
+        let (near, far) = if offset < 0.0 {
+            (node.left, node.right)
+        } else {
+            (node.right, node.left)
+        };
+        self.search(near, target, found);
+        if found.bound().is_none_or(|bound| offset.abs() <= bound) {
+            self.search(far, target, found);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{k_nearest, nearest, within_radius};
+    use crate::rand::{Rng, XorShiftRng};
+
+    fn random_points(rng: &mut XorShiftRng, n: usize) -> Vec<Euclidean<[i32; 3]>> {
+        // A small coordinate range, so there are plenty of ties to break.
+        (0..n)
+            .map(|_| Euclidean([0; 3].map(|_: i32| rng.below(20) as i32)))
+            .collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut rng = XorShiftRng::seed_from_u64(30);
+        for round in 0..50 {
+            let points = random_points(&mut rng, round * 7);
+            let bulk = KdTree::bulk_load(points.clone());
+            let mut grown = KdTree::new();
+            for p in &points {
+                grown.insert(*p);
+            }
+
+            for _ in 0..20 {
+                let target = random_points(&mut rng, 1)[0];
+                let k = rng.below(10) as usize;
+                let radius = rng.below(8) as f64;
+                for tree in [&bulk, &grown] {
+                    assert_eq!(tree.nearest(&target), nearest(&target, &points));
+                    assert_eq!(tree.k_nearest(&target, k), k_nearest(&target, &points, k));
+                    assert_eq!(tree.within_radius(&target, radius),
+                               within_radius(&target, &points, radius));
+                }
+            }
+        }
+    }
+
+    /// The most nodes on any path down from the root.
+    fn depth<P: MeasureDistance>(tree: &KdTree<P>) -> usize {
+        let mut deepest = 0;
+        let mut stack: Vec<(usize, usize)> = tree.root.map(|root| (root, 1)).into_iter().collect();
+        while let Some((n, d)) = stack.pop() {
+            deepest = deepest.max(d);
+            let node = &tree.nodes[n];
+            stack.extend(node.left.into_iter().chain(node.right).map(|child| (child, d + 1)));
+        }
+        deepest
+    }
+
+    #[test]
+    fn test_stays_shallow() {
+        // Sorted points and copies of one point would both make a chain of
+        // a tree without rebalancing.
+        let sorted: Vec<_> = (0..20_000).map(|i| Euclidean([i, i])).collect();
+        let copies = vec![Euclidean([3, 4]); 20_000];
+        for points in [sorted, copies] {
+            let bulk = KdTree::bulk_load(points.clone());
+            let mut grown = KdTree::new();
+            for p in &points {
+                grown.insert(*p);
+            }
+            let target = Euclidean([100, 90]);
+            for tree in [&bulk, &grown] {
+                // Log base 1 / 0.7 of 20,000 is under 28.
+                assert!(depth(tree) <= 32, "{} deep", depth(tree));
+                assert_eq!(tree.k_nearest(&target, 3), k_nearest(&target, &points, 3));
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "at least one dimension")]
+    fn test_no_dimensions() {
+        KdTree::bulk_load(vec![Euclidean([0.0f64; 0])]);
+    }
+}
@@ -0,0 +1,25 @@
+// Working versions of the sketches in main.rs. The notes there show the shape
+// of each trait; the modules here make them compile and run.
+
+pub mod mapreduce;
+pub mod query;
+pub mod window;
+pub mod distance;
+pub mod rand;
+pub mod kdtree;
+pub mod vptree;
+pub mod bktree;
+pub mod lsh;
+pub mod geometry;
+pub mod pancake;
+pub mod salad;
+pub mod canvas;
+pub mod game;
+pub mod scene;
+pub mod spatial;
+pub mod world;
+pub mod behavior;
+pub mod path;
+pub mod terminal;
+pub mod export;
+pub mod sprite;
@@ -0,0 +1,369 @@
+//! Locality-sensitive hashing: approximate nearest neighbours for
+//! high-dimensional vectors, where exact indexes degrade to brute force.
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::distance::{k_nearest, resolve, Collector, Cosine, Euclidean, KBest, MeasureDistance};
+use crate::rand::{Rand, Rng, StandardNormal};
+
+/// Which family of hash functions suits a vector type's metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Family {
+    /// Random hyperplanes: each hash is the side of a hyperplane through the
+    /// origin the vector falls on. Matches `Cosine`.
+    Hyperplane,
+    /// p-stable projections: each hash is a Gaussian projection, shifted and
+    /// cut into buckets of `bucket_width`. Matches `Euclidean`.
+    PStable,
+}
+
+/// A vector the index can hash.
+pub trait Embedding: MeasureDistance<Distance=f64> + Sized {
+    const FAMILY: Family;
+
+    fn components(&self) -> &[f64];
+    fn from_components(components: Vec<f64>) -> Self;
+}
+
+impl Embedding for Cosine<Vec<f64>> {
+    const FAMILY: Family = Family::Hyperplane;
+
+    fn components(&self) -> &[f64] {
+        &self.0
+    }
+
+    fn from_components(components: Vec<f64>) -> Self {
+        Cosine(components)
+    }
+}
+
+impl Embedding for Euclidean<Vec<f64>> {
+    const FAMILY: Family = Family::PStable;
+
+    fn components(&self) -> &[f64] {
+        &self.0
+    }
+
+    fn from_components(components: Vec<f64>) -> Self {
+        Euclidean(components)
+    }
+}
+
+/// How many hash tables, how many hashes are concatenated into each table's
+/// key, and (for `PStable`) how wide each bucket is.
+///
+/// More hashes per table make buckets more selective; more tables win back
+/// the recall that costs.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct LshParams {
+    pub tables: usize,
+    pub hashes_per_table: usize,
+    pub bucket_width: f64,
+}
+
+impl Default for LshParams {
+    fn default() -> LshParams {
+        LshParams { tables: 8, hashes_per_table: 12, bucket_width: 4.0 }
+    }
+}
+
+/// An approximate nearest-neighbour index. Queries look only at the points
+/// that share a bucket with the target in at least one table, then rank
+/// those exactly.
+#[derive(Clone, Debug)]
+pub struct LshIndex<P> {
+    params: LshParams,
+    dimensions: usize,
+    /// One row of `dimensions` weights per hash, table after table.
+    projections: Vec<f64>,
+    /// One offset per hash (only used by `PStable`).
+    offsets: Vec<f64>,
+    points: Vec<P>,
+    buckets: Vec<HashMap<u64, Vec<usize>>>,
+}
+
+const MAGIC: &[u8; 4] = b"LSH1";
+
+impl<P: Embedding> LshIndex<P> {
+    /// An empty index for `dimensions`-long vectors, with hash functions
+    /// drawn from `rng`. The same seed gives the same index.
+    pub fn new<R: Rng>(dimensions: usize, params: LshParams, rng: &mut R) -> LshIndex<P> {
+        assert!(params.tables > 0 && params.hashes_per_table > 0,
+                "need at least one table and one hash per table");
+        if P::FAMILY == Family::Hyperplane {
+            assert!(params.hashes_per_table <= 64, "hyperplane keys hold at most 64 hashes");
+        } else {
+            assert!(params.bucket_width > 0.0 && params.bucket_width.is_finite(),
+                    "bucket width must be positive");
+        }
+
+        let hashes = params.tables * params.hashes_per_table;
+        let projections = (0..hashes * dimensions)
+            .map(|_| StandardNormal::rand(rng).0)
+            .collect();
+        let offsets = (0..hashes)
+            .map(|_| f64::rand(rng) * params.bucket_width)
+            .collect();
+        LshIndex::with_hashes(dimensions, params, projections, offsets)
+    }
+
+    fn with_hashes(dimensions: usize, params: LshParams, projections: Vec<f64>,
+                   offsets: Vec<f64>) -> LshIndex<P> {
+        LshIndex {
+            params,
+            dimensions,
+            projections,
+            offsets,
+            points: vec![],
+            buckets: vec![HashMap::new(); params.tables],
+        }
+    }
+
+    pub fn params(&self) -> LshParams {
+        self.params
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The points in insertion order.
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    fn key(&self, table: usize, v: &[f64]) -> u64 {
+        let k = self.params.hashes_per_table;
+        let mut key: u64 = 0;
+        for h in table * k..(table + 1) * k {
+            let row = &self.projections[h * self.dimensions..(h + 1) * self.dimensions];
+            let projection: f64 = row.iter().zip(v).map(|(a, x)| a * x).sum();
+            key = match P::FAMILY {
+                Family::Hyperplane => (key << 1) | (projection >= 0.0) as u64,
+                Family::PStable => {
+                    let bucket = ((projection + self.offsets[h]) / self.params.bucket_width)
+                        .floor() as i64;
+                    // FNV-1a style mixing of the bucket numbers.
+                    (key ^ bucket as u64).wrapping_mul(0x0000_0100_0000_01b3)
+                }
+            };
+        }
+        key
+    }
+
+    pub fn insert(&mut self, point: P) {
+        assert_eq!(point.components().len(), self.dimensions, "wrong number of dimensions");
+        let index = self.points.len();
+        for table in 0..self.params.tables {
+            let key = self.key(table, point.components());
+            self.buckets[table].entry(key).or_default().push(index);
+        }
+        self.points.push(point);
+    }
+
+    /// Indexes of the points sharing a bucket with `target` in any table.
+    fn candidates(&self, target: &P) -> Vec<usize> {
+        let mut found = vec![];
+        for table in 0..self.params.tables {
+            if let Some(bucket) = self.buckets[table].get(&self.key(table, target.components())) {
+                found.extend_from_slice(bucket);
+            }
+        }
+        found.sort_unstable();
+        found.dedup();
+        found
+    }
+
+    /// The closest point to `target` among those hashed near it.
+    pub fn nearest(&self, target: &P) -> Option<(&P, f64)> {
+        self.k_nearest(target, 1).into_iter().next()
+    }
+
+    /// Up to `k` points close to `target`, nearest first. These are the
+    /// exact best among the candidates, but a true neighbour that never
+    /// shared a bucket with `target` will be missed.
+    pub fn k_nearest(&self, target: &P, k: usize) -> Vec<(&P, f64)> {
+        let mut best = KBest::new(k);
+        for i in self.candidates(target) {
+            best.offer(target.distance(&self.points[i]), i);
+        }
+        resolve(&self.points, best.into_sorted_vec())
+    }
+
+    /// The fraction of exact `k` nearest neighbours the index finds, averaged
+    /// over `queries`: a way to choose `LshParams` for a data set.
+    pub fn recall(&self, queries: &[P], k: usize) -> f64 {
+        let mut hits = 0;
+        let mut wanted = 0;
+        for q in queries {
+            let exact = k_nearest(q, &self.points, k);
+            let approx = self.k_nearest(q, k);
+            wanted += exact.len();
+            hits += exact.iter()
+                .filter(|(p, _)| approx.iter().any(|(a, _)| std::ptr::eq(*a, *p)))
+                .count();
+        }
+        if wanted == 0 {
+            1.0
+        } else {
+            hits as f64 / wanted as f64
+        }
+    }
+
+    /// Write the hash functions and points in a compact little-endian binary
+    /// format. Buckets aren't stored; `load` rebuilds them.
+    pub fn save<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_all(&[P::FAMILY as u8])?;
+        for n in [self.params.tables, self.params.hashes_per_table, self.dimensions,
+                  self.points.len()] {
+            out.write_all(&(n as u64).to_le_bytes())?;
+        }
+        out.write_all(&self.params.bucket_width.to_le_bytes())?;
+        let points = self.points.iter().flat_map(|p| p.components());
+        for x in self.projections.iter().chain(&self.offsets).chain(points) {
+            out.write_all(&x.to_le_bytes())?;
+        }
+        out.flush()
+    }
+
+    /// Read an index written by `save`.
+    pub fn load<R: Read>(input: &mut R) -> io::Result<LshIndex<P>> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+        let mut magic = [0; 5];
+        input.read_exact(&mut magic)?;
+        if &magic[..4] != MAGIC {
+            return Err(invalid("not an LSH index file"));
+        }
+        if magic[4] != P::FAMILY as u8 {
+            return Err(invalid("index was built for a different hash family"));
+        }
+
+        let mut word = [0; 8];
+        let mut read_u64 = |input: &mut R| -> io::Result<usize> {
+            input.read_exact(&mut word)?;
+            usize::try_from(u64::from_le_bytes(word)).map_err(|_| invalid("size out of range"))
+        };
+        let tables = read_u64(input)?;
+        let hashes_per_table = read_u64(input)?;
+        let dimensions = read_u64(input)?;
+        let count = read_u64(input)?;
+        let mut read_f64s = |input: &mut R, n: usize| -> io::Result<Vec<f64>> {
+            let mut values = Vec::with_capacity(n.min(1 << 20));
+            for _ in 0..n {
+                input.read_exact(&mut word)?;
+                values.push(f64::from_le_bytes(word));
+            }
+            Ok(values)
+        };
+        let bucket_width = read_f64s(input, 1)?[0];
+        if tables == 0 || hashes_per_table == 0 {
+            return Err(invalid("index has no hash functions"));
+        }
+        if P::FAMILY == Family::Hyperplane {
+            if hashes_per_table > 64 {
+                return Err(invalid("hyperplane keys hold at most 64 hashes"));
+            }
+        } else if !(bucket_width.is_finite() && bucket_width > 0.0) {
+            return Err(invalid("bucket width must be positive"));
+        }
+        // Every point takes at least one value to store, so a file can't
+        // make us loop over points that aren't there.
+        if dimensions == 0 {
+            return Err(invalid("points have no dimensions"));
+        }
+        let hashes = tables.checked_mul(hashes_per_table)
+            .filter(|h| h.checked_mul(dimensions).is_some())
+            .ok_or_else(|| invalid("size out of range"))?;
+
+        let projections = read_f64s(input, hashes * dimensions)?;
+        let offsets = read_f64s(input, hashes)?;
+        let params = LshParams { tables, hashes_per_table, bucket_width };
+        let mut index = LshIndex::with_hashes(dimensions, params, projections, offsets);
+        for _ in 0..count {
+            index.insert(P::from_components(read_f64s(input, dimensions)?));
+        }
+        Ok(index)
+    }
+
+    pub fn save_file<Q: AsRef<Path>>(&self, path: Q) -> io::Result<()> {
+        self.save(&mut BufWriter::new(File::create(path)?))
+    }
+
+    pub fn load_file<Q: AsRef<Path>>(path: Q) -> io::Result<LshIndex<P>> {
+        LshIndex::load(&mut BufReader::new(File::open(path)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::nearest;
+    use crate::rand::XorShiftRng;
+
+    fn random_vector(rng: &mut XorShiftRng, dims: usize) -> Vec<f64> {
+        (0..dims).map(|_| StandardNormal::rand(rng).0).collect()
+    }
+
+    #[test]
+    fn test_finds_near_duplicates() {
+        let mut rng = XorShiftRng::seed_from_u64(32);
+        let mut index = LshIndex::new(256, LshParams::default(), &mut rng);
+        let base: Vec<Vec<f64>> = (0..300).map(|_| random_vector(&mut rng, 256)).collect();
+        for v in &base {
+            index.insert(Cosine(v.clone()));
+        }
+
+        // Slightly perturbed copies of indexed vectors should find their
+        // originals, just as exact search does.
+        let queries: Vec<_> = base.iter().take(50)
+            .map(|v| Cosine(v.iter().map(|x| x + 0.1 * StandardNormal::rand(&mut rng).0).collect()))
+            .collect();
+        for q in &queries {
+            assert_eq!(index.nearest(q), nearest(q, index.points()));
+        }
+        assert_eq!(index.recall(&queries, 1), 1.0);
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let mut rng = XorShiftRng::seed_from_u64(32);
+        let params = LshParams { tables: 4, hashes_per_table: 6, bucket_width: 2.0 };
+        let mut index = LshIndex::new(16, params, &mut rng);
+        for _ in 0..100 {
+            index.insert(Euclidean(random_vector(&mut rng, 16)));
+        }
+
+        let mut bytes = vec![];
+        index.save(&mut bytes).unwrap();
+        let loaded: LshIndex<Euclidean<Vec<f64>>> = LshIndex::load(&mut &bytes[..]).unwrap();
+        let q = Euclidean(random_vector(&mut rng, 16));
+        assert_eq!(loaded.k_nearest(&q, 5), index.k_nearest(&q, 5));
+
+        let err = LshIndex::<Cosine<Vec<f64>>>::load(&mut &bytes[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Corrupt headers are rejected before anything is read for them.
+        let corrupt = |at: usize, value: [u8; 8]| {
+            let mut bytes = bytes.clone();
+            bytes[at..at + 8].copy_from_slice(&value);
+            LshIndex::<Euclidean<Vec<f64>>>::load(&mut &bytes[..]).unwrap_err().kind()
+        };
+        assert_eq!(corrupt(37, f64::NAN.to_le_bytes()), io::ErrorKind::InvalidData);
+        assert_eq!(corrupt(37, (-2.0f64).to_le_bytes()), io::ErrorKind::InvalidData);
+        let mut huge = bytes.clone();
+        huge[21..29].copy_from_slice(&0u64.to_le_bytes());
+        huge[29..37].copy_from_slice(&u64::MAX.to_le_bytes());
+        let err = LshIndex::<Euclidean<Vec<f64>>>::load(&mut &huge[..]).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
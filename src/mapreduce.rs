@@ -0,0 +1,460 @@
+//! A tiny in-memory map/reduce engine: the `run_query` sketched in main.rs,
+//! made real.
+
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// A single cell of a data set.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Str(String),
+}
+
+impl Value {
+    /// Numeric view of this value, if it has one.
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Int(i) => Some(i as f64),
+            Value::Float(f) => Some(f),
+            _ => None,
+        }
+    }
+
+    fn rank(&self) -> u8 {
+        match *self {
+            Value::Null => 0,
+            Value::Bool(_) => 1,
+            Value::Int(_) | Value::Float(_) => 2,
+            Value::Str(_) => 3,
+        }
+    }
+}
+
+/// Compare two floats, with every NaN equal and after every other number,
+/// and -0.0 equal to 0.0.
+fn cmp_floats(a: f64, b: f64) -> Ordering {
+    match (a.is_nan(), b.is_nan()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Greater,
+        (false, true) => Ordering::Less,
+        (false, false) => a.partial_cmp(&b).unwrap(),
+    }
+}
+
+/// Compare an integer with a float exactly. Converting either to the other
+/// would round large values, making equality intransitive.
+fn cmp_int_float(i: i64, x: f64) -> Ordering {
+    // 2^63, which is exactly representable; every float in [-2^63, 2^63)
+    // has an integer part that fits in an i64.
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if x.is_nan() || x >= LIMIT {
+        return Ordering::Less;
+    }
+    if x < -LIMIT {
+        return Ordering::Greater;
+    }
+    let whole = x.trunc();
+    i.cmp(&(whole as i64)).then_with(|| cmp_floats(0.0, x - whole))
+}
+
+// Values have to be usable as keys, so we give them a total order: Null
+// sorts first, numbers compare exactly by value whether they're Int or
+// Float, and NaN sorts after every other number.
+impl Ord for Value {
+    fn cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Float(a), Value::Float(b)) => cmp_floats(*a, *b),
+            (Value::Int(a), Value::Float(b)) => cmp_int_float(*a, *b),
+            (Value::Float(a), Value::Int(b)) => cmp_int_float(*b, *a).reverse(),
+            (Value::Str(a), Value::Str(b)) => a.cmp(b),
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Value) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Value {
+    fn eq(&self, other: &Value) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Value {}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Null => write!(f, "null"),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Int(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Str(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl From<i64> for Value {
+    fn from(i: i64) -> Value {
+        Value::Int(i)
+    }
+}
+
+impl From<f64> for Value {
+    fn from(x: f64) -> Value {
+        Value::Float(x)
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Value {
+        Value::Bool(b)
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Value {
+        Value::Str(s.to_string())
+    }
+}
+
+pub type Row = Vec<Value>;
+
+/// The grouping key a `Mapper` emits along with each value.
+pub type Key = Vec<Value>;
+
+/// One output row per distinct key, sorted by key.
+pub type Results = Vec<(Key, Row)>;
+
+/// A large, partitioned data set. Every row in every partition has one
+/// value per column.
+#[derive(Clone, Debug)]
+pub struct DataSet {
+    pub name: String,
+    pub columns: Vec<String>,
+    pub partitions: Vec<Vec<Row>>,
+}
+
+impl DataSet {
+    pub fn new(name: &str, columns: &[&str]) -> DataSet {
+        DataSet {
+            name: name.to_string(),
+            columns: columns.iter().map(|c| c.to_string()).collect(),
+            partitions: vec![],
+        }
+    }
+
+    /// Add a partition of rows. Panics if a row has the wrong width.
+    pub fn add_partition(&mut self, rows: Vec<Row>) {
+        for row in &rows {
+            assert_eq!(row.len(), self.columns.len(),
+                       "row width doesn't match the data set's columns");
+        }
+        self.partitions.push(rows);
+    }
+
+    /// Position of the column called `name`.
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.columns.iter().position(|c| c == name)
+    }
+
+    /// Iterate over every row in every partition.
+    pub fn rows(&self) -> impl Iterator<Item=&Row> {
+        self.partitions.iter().flatten()
+    }
+}
+
+/// The map half of a query: look at one row and emit zero or more
+/// `(key, values)` pairs.
+pub trait Mapper {
+    fn map(&self, row: &[Value], emit: &mut dyn FnMut(Key, Row));
+}
+
+/// The reduce half of a query. Accumulators are built per partition and then
+/// merged, so `merge` must agree with calling `step` on both halves' inputs.
+pub trait Reducer {
+    type Acc;
+
+    /// A fresh accumulator for a newly seen key.
+    fn start(&self) -> Self::Acc;
+
+    /// Fold one emitted row into an accumulator.
+    fn step(&self, acc: &mut Self::Acc, values: &[Value]);
+
+    /// Combine two accumulators for the same key.
+    fn merge(&self, into: &mut Self::Acc, other: Self::Acc);
+
+    /// Turn a finished accumulator into output columns.
+    fn finish(&self, acc: Self::Acc) -> Row;
+}
+
+impl<M: Mapper + ?Sized> Mapper for &M {
+    fn map(&self, row: &[Value], emit: &mut dyn FnMut(Key, Row)) {
+        (**self).map(row, emit)
+    }
+}
+
+impl<R: Reducer + ?Sized> Reducer for &R {
+    type Acc = R::Acc;
+
+    fn start(&self) -> R::Acc {
+        (**self).start()
+    }
+
+    fn step(&self, acc: &mut R::Acc, values: &[Value]) {
+        (**self).step(acc, values)
+    }
+
+    fn merge(&self, into: &mut R::Acc, other: R::Acc) {
+        (**self).merge(into, other)
+    }
+
+    fn finish(&self, acc: R::Acc) -> Row {
+        (**self).finish(acc)
+    }
+}
+
+/// Run a query on a large, partitioned data set.
+/// See <http://research.google.com/archive/mapreduce.html>.
+pub fn run_query<M, R>(data: &DataSet, map: M, reduce: R) -> Results
+    where M: Mapper,
+          R: Reducer
+{
+    let mut totals: BTreeMap<Key, R::Acc> = BTreeMap::new();
+
+    for partition in &data.partitions {
+        // Combine within the partition first, as a real worker would, then
+        // merge the partial results into the totals.
+        let mut partial: BTreeMap<Key, R::Acc> = BTreeMap::new();
+        for row in partition {
+            map.map(row, &mut |key, values| {
+                let acc = partial.entry(key).or_insert_with(|| reduce.start());
+                reduce.step(acc, &values);
+            });
+        }
+
+        for (key, acc) in partial {
+            match totals.get_mut(&key) {
+                Some(total) => reduce.merge(total, acc),
+                None => {
+                    totals.insert(key, acc);
+                }
+            }
+        }
+    }
+
+    totals.into_iter()
+        .map(|(key, acc)| (key, reduce.finish(acc)))
+        .collect()
+}
+
+/// The built-in aggregate functions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AggregateFn {
+    Count,
+    Sum,
+    Min,
+    Max,
+    Avg,
+}
+
+impl AggregateFn {
+    pub fn from_name(name: &str) -> Option<AggregateFn> {
+        match name.to_ascii_lowercase().as_str() {
+            "count" => Some(AggregateFn::Count),
+            "sum" => Some(AggregateFn::Sum),
+            "min" => Some(AggregateFn::Min),
+            "max" => Some(AggregateFn::Max),
+            "avg" => Some(AggregateFn::Avg),
+            _ => None,
+        }
+    }
+}
+
+/// A built-in aggregate reading one column of each emitted row. With
+/// `input: None` it counts rows, which is what `count(*)` means.
+#[derive(Clone, Debug)]
+pub struct Aggregate {
+    pub func: AggregateFn,
+    pub input: Option<usize>,
+}
+
+/// Running state for one `Aggregate`.
+#[derive(Clone, Debug)]
+pub struct AggState {
+    count: i64,
+    sum: f64,
+    /// The exact total, while every value summed is an `Int` and the total
+    /// fits in one.
+    int_sum: Option<i64>,
+    best: Option<Value>,
+}
+
+impl Reducer for Aggregate {
+    type Acc = AggState;
+
+    fn start(&self) -> AggState {
+        AggState { count: 0, sum: 0.0, int_sum: Some(0), best: None }
+    }
+
+    fn step(&self, acc: &mut AggState, values: &[Value]) {
+        let value = match self.input {
+            None => {
+                acc.count += 1;
+                return;
+            }
+            Some(i) => &values[i],
+        };
+
+        // Like SQL, aggregates over a column skip nulls.
+        if let Value::Null = value {
+            return;
+        }
+        if let AggregateFn::Sum | AggregateFn::Avg = self.func {
+            // Only numbers can be summed. A query plan rejects columns
+            // holding anything else; used directly, they're skipped like
+            // nulls.
+            let x = match value.as_f64() {
+                Some(x) => x,
+                None => return,
+            };
+            acc.sum += x;
+            acc.int_sum = match value {
+                Value::Int(i) => acc.int_sum.and_then(|sum| sum.checked_add(*i)),
+                _ => None,
+            };
+        }
+        acc.count += 1;
+        let replace = match (&acc.best, self.func) {
+            (None, _) => true,
+            (Some(best), AggregateFn::Min) => value < best,
+            (Some(best), AggregateFn::Max) => value > best,
+            _ => false,
+        };
+        if replace {
+            acc.best = Some(value.clone());
+        }
+    }
+
+    fn merge(&self, into: &mut AggState, other: AggState) {
+        into.count += other.count;
+        into.sum += other.sum;
+        into.int_sum = match (into.int_sum, other.int_sum) {
+            (Some(a), Some(b)) => a.checked_add(b),
+            _ => None,
+        };
+        let replace = match (&into.best, &other.best, self.func) {
+            (_, None, _) => false,
+            (None, Some(_), _) => true,
+            (Some(a), Some(b), AggregateFn::Min) => b < a,
+            (Some(a), Some(b), AggregateFn::Max) => b > a,
+            _ => false,
+        };
+        if replace {
+            into.best = other.best;
+        }
+    }
+
+    fn finish(&self, acc: AggState) -> Row {
+        let value = match self.func {
+            AggregateFn::Count => Value::Int(acc.count),
+            AggregateFn::Sum | AggregateFn::Avg if acc.count == 0 => Value::Null,
+            // Past the range of an Int, the total is only approximate.
+            AggregateFn::Sum => acc.int_sum.map_or(Value::Float(acc.sum), Value::Int),
+            AggregateFn::Min | AggregateFn::Max => acc.best.unwrap_or(Value::Null),
+            AggregateFn::Avg => Value::Float(acc.sum / acc.count as f64),
+        };
+        vec![value]
+    }
+}
+
+/// Several reducers run side by side over the same emitted rows; the output
+/// row is their outputs concatenated.
+impl<R: Reducer> Reducer for Vec<R> {
+    type Acc = Vec<R::Acc>;
+
+    fn start(&self) -> Vec<R::Acc> {
+        self.iter().map(|r| r.start()).collect()
+    }
+
+    fn step(&self, acc: &mut Vec<R::Acc>, values: &[Value]) {
+        for (r, a) in self.iter().zip(acc.iter_mut()) {
+            r.step(a, values);
+        }
+    }
+
+    fn merge(&self, into: &mut Vec<R::Acc>, other: Vec<R::Acc>) {
+        for ((r, a), b) in self.iter().zip(into.iter_mut()).zip(other) {
+            r.merge(a, b);
+        }
+    }
+
+    fn finish(&self, acc: Vec<R::Acc>) -> Row {
+        self.iter().zip(acc).flat_map(|(r, a)| r.finish(a)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Emit each row keyed on its first column, passing the second along.
+    struct ByFirst;
+
+    impl Mapper for ByFirst {
+        fn map(&self, row: &[Value], emit: &mut dyn FnMut(Key, Row)) {
+            emit(vec![row[0].clone()], vec![row[1].clone()]);
+        }
+    }
+
+    #[test]
+    fn test_run_query_merges_partitions() {
+        let mut data = DataSet::new("data", &["k", "v"]);
+        data.add_partition(vec![vec!["a".into(), 1.into()],
+                                vec!["b".into(), 5.into()]]);
+        data.add_partition(vec![vec!["a".into(), 3.into()]]);
+
+        let reduce = vec![Aggregate { func: AggregateFn::Count, input: None },
+                          Aggregate { func: AggregateFn::Sum, input: Some(0) },
+                          Aggregate { func: AggregateFn::Max, input: Some(0) }];
+        let results = run_query(&data, ByFirst, reduce);
+        assert_eq!(results, vec![
+            (vec!["a".into()], vec![2.into(), 4.into(), 3.into()]),
+            (vec!["b".into()], vec![1.into(), 5.into(), 5.into()]),
+        ]);
+    }
+
+    #[test]
+    fn test_value_order() {
+        // Past 2^53 not every i64 is a float, but comparisons stay exact.
+        let big = 1i64 << 53;
+        assert_eq!(Value::Int(big), Value::Float(big as f64));
+        assert!(Value::Int(big + 1) > Value::Float(big as f64));
+        assert!(Value::Int(i64::MAX) < Value::Float(i64::MAX as f64));
+        assert!(Value::Int(-3) > Value::Float(-3.5) && Value::Int(-3) < Value::Float(-2.5));
+        assert!(Value::Float(f64::INFINITY) < Value::Float(-f64::NAN));
+        assert!(Value::Int(i64::MIN) > Value::Float(f64::NEG_INFINITY));
+
+        // 0.0 and -0.0 are the same key.
+        let mut data = DataSet::new("data", &["k", "v"]);
+        data.add_partition(vec![vec![Value::Float(0.0), 1.into()],
+                                vec![Value::Float(-0.0), 2.into()],
+                                vec![Value::Int(0), 4.into()]]);
+        let reduce = vec![Aggregate { func: AggregateFn::Sum, input: Some(0) }];
+        let results = run_query(&data, ByFirst, reduce);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].1, vec![7.into()]);
+    }
+}
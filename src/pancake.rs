@@ -0,0 +1,322 @@
+//! The `PancakeStack` sketched in "Generic Functions": a generic `push`
+//! over any `Topping`, and a `PancakeResult` type alias.
+
+use std::error::Error;
+use std::fmt;
+
+mod history;
+mod nutrition;
+pub mod recipe;
+
+pub use self::history::{Operation, Transaction};
+pub use self::nutrition::{Measure, Nutrition, Report, ReportRow};
+
+/// Broad families of toppings; the default compatibility rules are written
+/// in terms of these.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Kind {
+    Pancake,
+    Spread,
+    Liquid,
+    Fruit,
+    Garnish,
+}
+
+/// Lets a boxed `Topping` be cloned. There's no need to implement this by
+/// hand: every `Topping` that is `Clone` gets it.
+pub trait CloneTopping {
+    fn clone_topping(&self) -> Box<dyn Topping>;
+}
+
+impl<T: Topping + Clone + 'static> CloneTopping for T {
+    fn clone_topping(&self) -> Box<dyn Topping> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn Topping> {
+    fn clone(&self) -> Box<dyn Topping> {
+        self.clone_topping()
+    }
+}
+
+/// Anything that can go on a stack of pancakes, pancakes included.
+///
+/// The stack asks both neighbours before a push: the new topping must agree
+/// to sit on the current top, and the current top must agree to have it on
+/// top. The defaults only forbid spreads straight on the plate; override
+/// either method for stricter rules.
+pub trait Topping: fmt::Debug + CloneTopping {
+    /// A short lowercase name, used in errors.
+    fn name(&self) -> &str;
+
+    fn kind(&self) -> Kind;
+
+    /// Calories, weight and cost of one layer of this.
+    fn nutrition(&self) -> Nutrition;
+
+    /// Can this go on `below`? `None` means the bare plate.
+    fn can_go_on(&self, below: Option<&dyn Topping>) -> bool {
+        below.is_some() || self.kind() != Kind::Spread
+    }
+
+    /// Can `above` go directly on top of this?
+    fn accepts_on_top(&self, above: &dyn Topping) -> bool {
+        let _ = above;
+        true
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PancakeError {
+    /// The stack already holds as many layers as it can.
+    StackFull { capacity: usize },
+    /// `above` can't go directly on `below`.
+    IncompatibleTopping { below: String, above: String },
+    /// There's nothing on the plate.
+    Empty,
+}
+
+impl fmt::Display for PancakeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PancakeError::StackFull { capacity } => {
+                write!(f, "the stack is full; it holds at most {} layers", capacity)
+            }
+            PancakeError::IncompatibleTopping { below, above } => {
+                write!(f, "{} can't go on top of {}", above, below)
+            }
+            PancakeError::Empty => write!(f, "the stack is empty"),
+        }
+    }
+}
+
+impl Error for PancakeError {}
+
+pub type PancakeResult<T> = Result<T, PancakeError>;
+
+/// What's shown as the bottom layer in errors about the plate itself.
+const PLATE: &str = "the plate";
+
+/// A stack of pancakes and toppings, bottom first.
+///
+/// Every successful `push` and `pop` is recorded, so they can be undone and
+/// redone; see the `history` module.
+#[derive(Debug)]
+pub struct PancakeStack {
+    layers: Vec<Box<dyn Topping>>,
+    capacity: usize,
+    /// Operations currently in effect, oldest first.
+    log: Vec<Operation>,
+    /// Operations undone since the last new one, most recently undone last.
+    undone: Vec<Operation>,
+}
+
+impl PancakeStack {
+    /// An empty stack that holds at most `capacity` layers. Room for them
+    /// is only allocated as they're pushed, so any capacity will do.
+    pub fn with_capacity(capacity: usize) -> PancakeStack {
+        PancakeStack {
+            layers: Vec::new(),
+            capacity,
+            log: vec![],
+            undone: vec![],
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.layers.len() >= self.capacity
+    }
+
+    /// Put `goop` on top, if there's room and it's compatible with the
+    /// current top layer.
+    pub fn push<T: Topping + 'static>(&mut self, goop: T) -> PancakeResult<()> {
+        self.push_boxed(Box::new(goop))
+    }
+
+    /// `push` for toppings whose type is only known at run time.
+    pub fn push_boxed(&mut self, goop: Box<dyn Topping>) -> PancakeResult<()> {
+        self.check(&*goop)?;
+        self.record(Operation::Push(goop.clone()));
+        self.layers.push(goop);
+        Ok(())
+    }
+
+    /// Would `goop` be accepted by `push` right now?
+    pub fn check(&self, goop: &dyn Topping) -> PancakeResult<()> {
+        if self.is_full() {
+            return Err(PancakeError::StackFull { capacity: self.capacity });
+        }
+        let top = self.layers.last().map(|t| &**t);
+        let fits = goop.can_go_on(top) && top.is_none_or(|t| t.accepts_on_top(goop));
+        if !fits {
+            return Err(PancakeError::IncompatibleTopping {
+                below: top.map_or(PLATE, |t| t.name()).to_string(),
+                above: goop.name().to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// Take off the top layer.
+    pub fn pop(&mut self) -> PancakeResult<Box<dyn Topping>> {
+        let top = self.layers.pop().ok_or(PancakeError::Empty)?;
+        self.record(Operation::Pop(top.clone()));
+        Ok(top)
+    }
+
+    /// Look at the top layer without removing it.
+    pub fn peek(&self) -> PancakeResult<&dyn Topping> {
+        self.layers.last().map(|t| &**t).ok_or(PancakeError::Empty)
+    }
+
+    /// The layers, bottom first.
+    pub fn layers(&self) -> impl Iterator<Item=&dyn Topping> {
+        self.layers.iter().map(|t| &**t)
+    }
+}
+
+/// A plain pancake.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pancake;
+
+impl Topping for Pancake {
+    fn name(&self) -> &str {
+        "pancake"
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Pancake
+    }
+
+    fn nutrition(&self) -> Nutrition {
+        Nutrition::new(90, 38, 25)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Butter;
+
+impl Topping for Butter {
+    fn name(&self) -> &str {
+        "butter"
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Spread
+    }
+
+    fn nutrition(&self) -> Nutrition {
+        Nutrition::new(36, 5, 10)
+    }
+
+    /// Butter only spreads on a warm pancake.
+    fn can_go_on(&self, below: Option<&dyn Topping>) -> bool {
+        below.is_some_and(|b| b.kind() == Kind::Pancake)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Syrup;
+
+impl Topping for Syrup {
+    fn name(&self) -> &str {
+        "syrup"
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Liquid
+    }
+
+    fn nutrition(&self) -> Nutrition {
+        Nutrition::new(52, 20, 30)
+    }
+
+    /// A pancake laid on syrup goes soggy.
+    fn accepts_on_top(&self, above: &dyn Topping) -> bool {
+        above.kind() != Kind::Pancake
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Blueberries;
+
+impl Topping for Blueberries {
+    fn name(&self) -> &str {
+        "blueberries"
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Fruit
+    }
+
+    fn nutrition(&self) -> Nutrition {
+        Nutrition::new(21, 37, 60)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WhippedCream;
+
+impl Topping for WhippedCream {
+    fn name(&self) -> &str {
+        "whipped cream"
+    }
+
+    fn kind(&self) -> Kind {
+        Kind::Garnish
+    }
+
+    fn nutrition(&self) -> Nutrition {
+        Nutrition::new(26, 10, 20)
+    }
+
+    /// Cream is the crown of the stack: only fruit and other garnishes can
+    /// go on it.
+    fn accepts_on_top(&self, above: &dyn Topping) -> bool {
+        matches!(above.kind(), Kind::Fruit | Kind::Garnish)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_pop_peek() {
+        let mut stack = PancakeStack::with_capacity(4);
+        assert_eq!(stack.peek().unwrap_err(), PancakeError::Empty);
+        assert_eq!(stack.push(Butter), Err(PancakeError::IncompatibleTopping {
+            below: "the plate".to_string(),
+            above: "butter".to_string(),
+        }));
+
+        stack.push(Pancake).unwrap();
+        stack.push(Butter).unwrap();
+        stack.push(Syrup).unwrap();
+        let err = stack.push(Pancake).unwrap_err();
+        assert_eq!(err.to_string(), "pancake can't go on top of syrup");
+
+        stack.push(WhippedCream).unwrap();
+        assert_eq!(stack.push(Blueberries), Err(PancakeError::StackFull { capacity: 4 }));
+        assert_eq!(stack.peek().unwrap().name(), "whipped cream");
+        assert_eq!(stack.pop().unwrap().name(), "whipped cream");
+        assert_eq!(stack.len(), 3);
+
+        let mut stack = PancakeStack::with_capacity(usize::MAX);
+        stack.push(Pancake).unwrap();
+        assert!(!stack.is_full());
+    }
+}
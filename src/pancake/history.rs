@@ -0,0 +1,221 @@
+//! Undo, redo, transactions and replay for `PancakeStack`.
+
+use super::{PancakeError, PancakeResult, PancakeStack, Topping};
+
+/// One recorded change to a stack, holding a copy of the layer involved.
+#[derive(Clone, Debug)]
+pub enum Operation {
+    Push(Box<dyn Topping>),
+    Pop(Box<dyn Topping>),
+}
+
+impl PancakeStack {
+    /// Log a new operation. Anything undone before it can no longer be
+    /// redone.
+    pub(super) fn record(&mut self, op: Operation) {
+        self.log.push(op);
+        self.undone.clear();
+    }
+
+    /// The operations currently in effect, oldest first.
+    pub fn log(&self) -> &[Operation] {
+        &self.log
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.log.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.undone.is_empty()
+    }
+
+    /// Reverse the most recent operation. Returns false if there was nothing
+    /// to undo.
+    pub fn undo(&mut self) -> bool {
+        match self.log.pop() {
+            Some(op) => {
+                self.unapply(&op);
+                self.undone.push(op);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Reapply the most recently undone operation. Returns false if there
+    /// was nothing to redo.
+    pub fn redo(&mut self) -> bool {
+        match self.undone.pop() {
+            Some(op) => {
+                // The stack is back where it was when `op` first succeeded,
+                // so it needs no checking this time.
+                match &op {
+                    Operation::Push(t) => self.layers.push(t.clone()),
+                    Operation::Pop(_) => {
+                        self.layers.pop();
+                    }
+                }
+                self.log.push(op);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn unapply(&mut self, op: &Operation) {
+        match op {
+            Operation::Push(_) => {
+                self.layers.pop();
+            }
+            Operation::Pop(t) => self.layers.push(t.clone()),
+        }
+    }
+
+    /// Run `body` as a transaction: if it fails, or any push or pop inside it
+    /// fails (even if `body` carries on regardless), every change it made is
+    /// rolled back and the first error is returned.
+    ///
+    /// ```
+    /// # use traits_generics::pancake::*;
+    /// let mut stack = PancakeStack::with_capacity(10);
+    /// let result = stack.transaction(|tx| {
+    ///     tx.push(Pancake)?;
+    ///     tx.push(Syrup)?;
+    ///     tx.push(Pancake) // soggy!
+    /// });
+    /// assert!(result.is_err());
+    /// assert!(stack.is_empty());
+    /// ```
+    pub fn transaction<F, T>(&mut self, body: F) -> PancakeResult<T>
+        where F: FnOnce(&mut Transaction) -> PancakeResult<T>
+    {
+        let mark = self.log.len();
+        let undone = self.undone.clone();
+        let mut tx = Transaction { stack: self, error: None };
+        let result = body(&mut tx);
+        let error = tx.error.take();
+
+        match (result, error) {
+            (Ok(value), None) => Ok(value),
+            (result, error) => {
+                while self.log.len() > mark {
+                    let op = self.log.pop().unwrap();
+                    self.unapply(&op);
+                }
+                self.undone = undone;
+                Err(error.unwrap_or_else(|| result.err().unwrap()))
+            }
+        }
+    }
+
+    /// Build a new stack by replaying `log` from empty. Every operation is
+    /// checked again, so a log edited by hand can still be rejected.
+    pub fn replay(log: &[Operation], capacity: usize) -> PancakeResult<PancakeStack> {
+        let mut stack = PancakeStack::with_capacity(capacity);
+        for op in log {
+            match op {
+                Operation::Push(t) => stack.push_boxed(t.clone())?,
+                Operation::Pop(_) => {
+                    stack.pop()?;
+                }
+            }
+        }
+        Ok(stack)
+    }
+}
+
+/// The view of a stack inside `PancakeStack::transaction`. It notes the
+/// first failure so the transaction can roll back.
+pub struct Transaction<'a> {
+    stack: &'a mut PancakeStack,
+    error: Option<PancakeError>,
+}
+
+impl<'a> Transaction<'a> {
+    fn note<T>(&mut self, result: PancakeResult<T>) -> PancakeResult<T> {
+        if let Err(e) = &result {
+            self.error.get_or_insert_with(|| e.clone());
+        }
+        result
+    }
+
+    pub fn push<T: Topping + 'static>(&mut self, goop: T) -> PancakeResult<()> {
+        let result = self.stack.push(goop);
+        self.note(result)
+    }
+
+    pub fn push_boxed(&mut self, goop: Box<dyn Topping>) -> PancakeResult<()> {
+        let result = self.stack.push_boxed(goop);
+        self.note(result)
+    }
+
+    pub fn pop(&mut self) -> PancakeResult<Box<dyn Topping>> {
+        let result = self.stack.pop();
+        self.note(result)
+    }
+
+    pub fn peek(&self) -> PancakeResult<&dyn Topping> {
+        self.stack.peek()
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::*;
+
+    fn names(stack: &PancakeStack) -> Vec<&str> {
+        stack.layers().map(|t| t.name()).collect()
+    }
+
+    #[test]
+    fn test_undo_redo_replay() {
+        let mut stack = PancakeStack::with_capacity(5);
+        stack.push(Pancake).unwrap();
+        stack.push(Syrup).unwrap();
+        stack.pop().unwrap();
+        stack.push(Butter).unwrap();
+        assert_eq!(names(&stack), ["pancake", "butter"]);
+
+        assert!(stack.undo());
+        assert!(stack.undo());
+        assert_eq!(names(&stack), ["pancake", "syrup"]);
+        assert!(stack.redo());
+        assert_eq!(names(&stack), ["pancake"]);
+
+        let copy = PancakeStack::replay(stack.log(), 5).unwrap();
+        assert_eq!(names(&copy), names(&stack));
+
+        // A new operation discards what was left to redo.
+        stack.push(Blueberries).unwrap();
+        assert!(!stack.redo());
+    }
+
+    #[test]
+    fn test_transaction_rolls_back() {
+        let mut stack = PancakeStack::with_capacity(5);
+        stack.push(Pancake).unwrap();
+        let result = stack.transaction(|tx| {
+            tx.push(Butter)?;
+            let _ = tx.push(Butter); // ignored, but still fails the transaction
+            tx.push(Syrup)
+        });
+        assert_eq!(result, Err(PancakeError::IncompatibleTopping {
+            below: "butter".to_string(),
+            above: "butter".to_string(),
+        }));
+        assert_eq!(names(&stack), ["pancake"]);
+        assert_eq!(stack.log().len(), 1);
+
+        stack.transaction(|tx| tx.push(Syrup)).unwrap();
+        assert_eq!(names(&stack), ["pancake", "syrup"]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Nutrition facts and cost, and reports that add them up over a stack.
+
+use std::io::{self, Write};
+use std::iter::Sum;
+use std::ops::Add;
+
+use super::{PancakeStack, Topping};
+
+/// What one layer weighs, provides and costs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Nutrition {
+    /// In kilocalories.
+    pub calories: u32,
+    pub grams: u32,
+    pub cost_cents: u32,
+}
+
+impl Nutrition {
+    pub const fn new(calories: u32, grams: u32, cost_cents: u32) -> Nutrition {
+        Nutrition { calories, grams, cost_cents }
+    }
+}
+
+/// Totals saturate rather than overflow, however tall the stack.
+impl Add for Nutrition {
+    type Output = Nutrition;
+
+    fn add(self, rhs: Nutrition) -> Nutrition {
+        Nutrition {
+            calories: self.calories.saturating_add(rhs.calories),
+            grams: self.grams.saturating_add(rhs.grams),
+            cost_cents: self.cost_cents.saturating_add(rhs.cost_cents),
+        }
+    }
+}
+
+impl Sum for Nutrition {
+    fn sum<I: Iterator<Item=Nutrition>>(iter: I) -> Nutrition {
+        iter.fold(Nutrition::default(), Add::add)
+    }
+}
+
+/// A quantity that can be totalled and shown in a report's columns.
+pub trait Measure: Add<Output=Self> + Default + Copy {
+    /// One heading per column this measure fills.
+    const HEADINGS: &'static [&'static str];
+
+    /// One cell per heading.
+    fn cells(&self) -> Vec<String>;
+}
+
+impl Measure for Nutrition {
+    const HEADINGS: &'static [&'static str] = &["calories", "grams", "cost"];
+
+    fn cells(&self) -> Vec<String> {
+        vec![
+            self.calories.to_string(),
+            self.grams.to_string(),
+            format!("{}.{:02}", self.cost_cents / 100, self.cost_cents % 100),
+        ]
+    }
+}
+
+macro_rules! plain_measure {
+    ($($t:ty),*) => {
+        $(
+            impl Measure for $t {
+                const HEADINGS: &'static [&'static str] = &["amount"];
+
+                fn cells(&self) -> Vec<String> {
+                    vec![self.to_string()]
+                }
+            }
+        )*
+    }
+}
+
+plain_measure!(u32, u64, i64, f64);
+
+/// One line of a `Report`: every layer of one topping.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReportRow<N> {
+    pub name: String,
+    pub count: usize,
+    pub amount: N,
+}
+
+/// A measure totalled per topping and over the whole stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Report<N> {
+    /// In order of each topping's lowest layer.
+    pub rows: Vec<ReportRow<N>>,
+    pub total: N,
+}
+
+impl<N: Measure> Report<N> {
+    /// Write the report as a tab-separated table with a heading line and a
+    /// final `total` line.
+    pub fn write_to<W: Write + ?Sized>(&self, out: &mut W) -> io::Result<()> {
+        let mut headings = vec!["topping", "count"];
+        headings.extend_from_slice(N::HEADINGS);
+        writeln!(out, "{}", headings.join("\t"))?;
+
+        let count = self.rows.iter().map(|r| r.count).sum::<usize>();
+        let totals = ReportRow { name: "total".to_string(), count, amount: self.total };
+        for row in self.rows.iter().chain(Some(&totals)) {
+            let mut cells = vec![row.name.clone(), row.count.to_string()];
+            cells.extend(row.amount.cells());
+            writeln!(out, "{}", cells.join("\t"))?;
+        }
+        Ok(())
+    }
+}
+
+impl PancakeStack {
+    /// Add up `measure` over every layer.
+    pub fn total<N, F>(&self, measure: F) -> N
+        where N: Add<Output=N> + Default, F: Fn(&dyn Topping) -> N
+    {
+        self.layers().fold(N::default(), |sum, t| sum + measure(t))
+    }
+
+    /// The nutrition facts and cost of the whole stack.
+    pub fn nutrition(&self) -> Nutrition {
+        self.total(|t| t.nutrition())
+    }
+
+    /// Total `measure` per topping name, and overall.
+    pub fn report<N, F>(&self, measure: F) -> Report<N>
+        where N: Measure, F: Fn(&dyn Topping) -> N
+    {
+        let mut rows: Vec<ReportRow<N>> = vec![];
+        for layer in self.layers() {
+            let amount = measure(layer);
+            match rows.iter_mut().find(|r| r.name == layer.name()) {
+                Some(row) => {
+                    row.count += 1;
+                    row.amount = row.amount + amount;
+                }
+                None => rows.push(ReportRow { name: layer.name().to_string(), count: 1, amount }),
+            }
+        }
+        let total = rows.iter().fold(N::default(), |sum, r| sum + r.amount);
+        Report { rows, total }
+    }
+
+    pub fn nutrition_report(&self) -> Report<Nutrition> {
+        self.report(|t| t.nutrition())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pancake::{Butter, Pancake, Syrup};
+
+    #[test]
+    fn test_reports() {
+        let mut stack = PancakeStack::with_capacity(5);
+        stack.push(Pancake).unwrap();
+        stack.push(Butter).unwrap();
+        stack.push(Pancake).unwrap();
+        stack.push(Syrup).unwrap();
+
+        assert_eq!(stack.nutrition(), Nutrition::new(268, 101, 90));
+        assert_eq!(stack.total(|t| f64::from(t.nutrition().calories) / 2.0), 134.0);
+
+        let mut out = vec![];
+        stack.nutrition_report().write_to(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "\
+topping\tcount\tcalories\tgrams\tcost
+pancake\t2\t180\t76\t0.50
+butter\t1\t36\t5\t0.10
+syrup\t1\t52\t20\t0.30
+total\t4\t268\t101\t0.90
+");
+
+        let calories = stack.report(|t| u64::from(t.nutrition().calories));
+        assert_eq!(calories.total, 268);
+        assert_eq!(calories.rows[0], ReportRow { name: "pancake".to_string(), count: 2, amount: 180 });
+
+        let huge = Nutrition::new(u32::MAX - 1, 0, 0);
+        assert_eq!((huge + Pancake.nutrition()).calories, u32::MAX);
+    }
+}
@@ -0,0 +1,334 @@
+//! A plain-text recipe format for pancake stacks.
+//!
+//! ```text
+//! # Sunday breakfast
+//! capacity: 8
+//! 3 x pancake
+//! butter          # melts nicely
+//! syrup
+//! whipped cream
+//! blueberries
+//! ```
+//!
+//! Layers are listed bottom first, one line each, optionally prefixed by a
+//! quantity (`3 x pancake` is three pancake layers in a row). `#` starts a
+//! comment, and blank lines are ignored. The optional `capacity:` line must
+//! come before any layers; without it the stack holds exactly the layers
+//! listed. No recipe makes more than `MAX_LAYERS` layers, though the
+//! capacity may be larger.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Write};
+
+use super::{Blueberries, Butter, Pancake, PancakeError, PancakeStack, Syrup, Topping, WhippedCream};
+
+/// A problem with a recipe, located at a 1-based line and column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecipeError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl RecipeError {
+    fn new(line: usize, column: usize, message: String) -> RecipeError {
+        RecipeError { line, column, message }
+    }
+}
+
+impl fmt::Display for RecipeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+impl Error for RecipeError {}
+
+pub type RecipeResult<T> = Result<T, RecipeError>;
+
+/// Maps the names used in recipes to toppings. Each name is backed by a
+/// prototype that is cloned for every layer.
+#[derive(Clone, Debug, Default)]
+pub struct ToppingRegistry {
+    prototypes: HashMap<String, Box<dyn Topping>>,
+}
+
+impl ToppingRegistry {
+    /// An empty registry.
+    pub fn new() -> ToppingRegistry {
+        ToppingRegistry::default()
+    }
+
+    /// A registry of every topping defined in this crate.
+    pub fn standard() -> ToppingRegistry {
+        let mut registry = ToppingRegistry::new();
+        registry.register(Pancake);
+        registry.register(Butter);
+        registry.register(Syrup);
+        registry.register(Blueberries);
+        registry.register(WhippedCream);
+        registry
+    }
+
+    /// Make `prototype` available under its `name()`, replacing any topping
+    /// already registered under that name.
+    pub fn register<T: Topping + 'static>(&mut self, prototype: T) {
+        self.register_boxed(Box::new(prototype));
+    }
+
+    pub fn register_boxed(&mut self, prototype: Box<dyn Topping>) {
+        self.prototypes.insert(prototype.name().to_string(), prototype);
+    }
+
+    /// A fresh topping called `name`, if there is one.
+    pub fn make(&self, name: &str) -> Option<Box<dyn Topping>> {
+        self.prototypes.get(name).cloned()
+    }
+}
+
+/// Column (1-based) of the byte offset `at` in `line`.
+fn column_of(line: &str, at: usize) -> usize {
+    line[..at].chars().count() + 1
+}
+
+/// The most layers a recipe can make, so a typo'd quantity can't eat all
+/// the memory there is.
+pub const MAX_LAYERS: usize = 10_000;
+
+/// Split a `N x ` quantity off the front of `line`, if it has one.
+fn split_quantity(line: &str) -> Option<(&str, &str)> {
+    let (quantity, rest) = line.split_once(" x ")?;
+    if !quantity.is_empty() && quantity.bytes().all(|b| b.is_ascii_digit()) {
+        Some((quantity, rest))
+    } else {
+        None
+    }
+}
+
+/// Would `parse` read `name`, alone on a line, as that name again?
+fn writable(name: &str) -> bool {
+    !name.is_empty()
+        && name.trim() == name
+        && !name.contains('#')
+        && !name.chars().any(char::is_control)
+        && !name.starts_with("capacity:")
+        && split_quantity(name).is_none()
+}
+
+/// Build a stack from a recipe, making each layer with `registry`.
+pub fn parse(text: &str, registry: &ToppingRegistry) -> RecipeResult<PancakeStack> {
+    let mut capacity = None;
+    // (line, column, topping) for every layer, so rejected pushes can be
+    // reported where they were written.
+    let mut layers = vec![];
+
+    for (i, raw) in text.lines().enumerate() {
+        let number = i + 1;
+        let line = raw.split('#').next().unwrap();
+        let start = line.len() - line.trim_start().len();
+        let body = line.trim();
+        if body.is_empty() {
+            continue;
+        }
+        let error = |at: usize, message: String| RecipeError::new(number, column_of(raw, at), message);
+
+        if let Some(rest) = body.strip_prefix("capacity:") {
+            if capacity.is_some() {
+                return Err(error(start, "capacity given twice".to_string()));
+            }
+            if !layers.is_empty() {
+                return Err(error(start, "capacity must come before the layers".to_string()));
+            }
+            let at = start + "capacity:".len() + (rest.len() - rest.trim_start().len());
+            let value = rest.trim();
+            let n = value.parse::<usize>().map_err(|_| {
+                error(at, format!("expected a number of layers, found `{}`", value))
+            })?;
+            capacity = Some(n);
+            continue;
+        }
+
+        // An optional `N x ` prefix.
+        let mut count = 1;
+        let mut name_at = start;
+        let mut name = body;
+        if let Some((quantity, rest)) = split_quantity(body) {
+            count = quantity.parse::<usize>()
+                .ok()
+                .filter(|&n| n > 0)
+                .ok_or_else(|| error(start, format!("bad quantity `{}`", quantity)))?;
+            name = rest.trim_start();
+            name_at = start + (body.len() - name.len());
+        }
+
+        let topping = registry.make(name)
+            .ok_or_else(|| error(name_at, format!("unknown topping `{}`", name)))?;
+        // Check the room left before making any layers.
+        if count > capacity.map_or(MAX_LAYERS, |c| c.min(MAX_LAYERS)) - layers.len() {
+            let message = match capacity {
+                Some(capacity) if capacity <= MAX_LAYERS => PancakeError::StackFull { capacity }.to_string(),
+                _ => format!("a recipe can't hold more than {} layers", MAX_LAYERS),
+            };
+            return Err(error(name_at, message));
+        }
+        for _ in 0..count {
+            layers.push((number, column_of(raw, name_at), topping.clone()));
+        }
+    }
+
+    let mut stack = PancakeStack::with_capacity(capacity.unwrap_or(layers.len()));
+    for (line, column, topping) in layers {
+        stack.push_boxed(topping).map_err(|e| RecipeError::new(line, column, e.to_string()))?;
+    }
+    Ok(stack)
+}
+
+impl PancakeStack {
+    /// Write this stack as a recipe that `parse` turns back into the same
+    /// stack, capacity included. Runs of the same topping are written with a
+    /// quantity.
+    ///
+    /// A stack that no recipe could make is an `InvalidInput` error, and
+    /// nothing is written: one of more than `MAX_LAYERS` layers, or with a
+    /// topping whose name would read as something else, such as one with a
+    /// `#` in it or starting with a quantity.
+    pub fn write_recipe<W: Write + ?Sized>(&self, out: &mut W) -> io::Result<()> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidInput, msg);
+        if self.len() > MAX_LAYERS {
+            return Err(invalid(format!("a recipe can't hold more than {} layers", MAX_LAYERS)));
+        }
+        if let Some(layer) = self.layers().find(|t| !writable(t.name())) {
+            return Err(invalid(format!("`{}` can't be written in a recipe", layer.name())));
+        }
+        writeln!(out, "capacity: {}", self.capacity)?;
+        let mut layers = self.layers().peekable();
+        while let Some(layer) = layers.next() {
+            let mut count = 1;
+            while layers.peek().is_some_and(|next| next.name() == layer.name()) {
+                layers.next();
+                count += 1;
+            }
+            if count > 1 {
+                writeln!(out, "{} x {}", count, layer.name())?;
+            } else {
+                writeln!(out, "{}", layer.name())?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn to_recipe(&self) -> io::Result<String> {
+        let mut out = vec![];
+        self.write_recipe(&mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pancake::{Kind, Nutrition};
+
+    const SUNDAY: &str = "\
+# Sunday breakfast
+capacity: 8
+2 x pancake
+butter          # melts nicely
+
+syrup
+whipped cream
+blueberries
+";
+
+    fn names(stack: &PancakeStack) -> Vec<&str> {
+        stack.layers().map(|t| t.name()).collect()
+    }
+
+    #[test]
+    fn test_parse_and_write() {
+        let registry = ToppingRegistry::standard();
+        let stack = parse(SUNDAY, &registry).unwrap();
+        assert_eq!(stack.capacity(), 8);
+        assert_eq!(names(&stack),
+                   ["pancake", "pancake", "butter", "syrup", "whipped cream", "blueberries"]);
+
+        let text = stack.to_recipe().unwrap();
+        assert_eq!(text, "capacity: 8\n2 x pancake\nbutter\nsyrup\nwhipped cream\nblueberries\n");
+        let again = parse(&text, &registry).unwrap();
+        assert_eq!(again.capacity(), 8);
+        assert_eq!(names(&again), names(&stack));
+
+        // Any capacity comes back, not just ones a recipe could fill.
+        let mut stack = PancakeStack::with_capacity(20_000);
+        stack.push(Pancake).unwrap();
+        let again = parse(&stack.to_recipe().unwrap(), &registry).unwrap();
+        assert_eq!(again.capacity(), 20_000);
+        assert_eq!(names(&again), ["pancake"]);
+    }
+
+    /// A topping called whatever a test likes.
+    #[derive(Clone, Debug)]
+    struct Named(&'static str);
+
+    impl Topping for Named {
+        fn name(&self) -> &str {
+            self.0
+        }
+
+        fn kind(&self) -> Kind {
+            Kind::Garnish
+        }
+
+        fn nutrition(&self) -> Nutrition {
+            Nutrition::default()
+        }
+    }
+
+    #[test]
+    fn test_unwritable_names() {
+        let mut registry = ToppingRegistry::new();
+        for name in ["no. 5", "2 x 4", "a #1", " padded", "capacity: 3", "line
+break", ""] {
+            registry.register(Named(name));
+            let mut stack = PancakeStack::with_capacity(2);
+            stack.push(Named(name)).unwrap();
+            match stack.to_recipe() {
+                // Whatever is written reads back the same.
+                Ok(text) => assert_eq!(names(&parse(&text, &registry).unwrap()), [name]),
+                Err(e) => {
+                    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+                    assert!(!writable(name));
+                }
+            }
+        }
+        assert!(writable("no. 5") && writable("2 x") && writable("x 4"));
+        assert!(!writable("2 x 4") && !writable("a #1"));
+
+        let mut stack = PancakeStack::with_capacity(MAX_LAYERS + 1);
+        for _ in 0..=MAX_LAYERS {
+            stack.push(Pancake).unwrap();
+        }
+        assert_eq!(stack.to_recipe().unwrap_err().kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn test_errors() {
+        let registry = ToppingRegistry::standard();
+        let err = |text| parse(text, &registry).unwrap_err().to_string();
+        assert_eq!(err("pancake\n  3 x waffle"), "line 2, column 7: unknown topping `waffle`");
+        assert_eq!(err("capacity: lots"), "line 1, column 11: expected a number of layers, found `lots`");
+        assert_eq!(err("pancake\ncapacity: 3"), "line 2, column 1: capacity must come before the layers");
+        assert_eq!(err("0 x pancake"), "line 1, column 1: bad quantity `0`");
+        assert_eq!(err("pancake\nsyrup\n pancake"), "line 3, column 2: pancake can't go on top of syrup");
+        assert_eq!(err("capacity: 1\n2 x pancake"),
+                   "line 2, column 5: the stack is full; it holds at most 1 layers");
+        assert_eq!(err("18446744073709551615 x pancake"),
+                   "line 1, column 24: a recipe can't hold more than 10000 layers");
+        assert_eq!(err("capacity: 3\npancake\n3 x syrup"),
+                   "line 3, column 5: the stack is full; it holds at most 3 layers");
+        assert_eq!(err("capacity: 99999999999\n10001 x pancake"),
+                   "line 2, column 9: a recipe can't hold more than 10000 layers");
+    }
+}
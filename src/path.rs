@@ -0,0 +1,444 @@
+//! Finding a way around the map: breadth-first search, Dijkstra and A* over
+//! a grid of cells, each with a cost to enter or blocked outright.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::game::{Creature, Direction, Visible};
+use crate::world::{CreatureId, World};
+
+/// The cost of a straight step onto a cell of cost 1. A diagonal step costs
+/// `DIAGONAL` instead, close to √2 times as much. Path costs are `u64`, so
+/// any cell cost times either fits.
+pub const STRAIGHT: u64 = 10;
+pub const DIAGONAL: u64 = 14;
+
+/// Which steps a creature may take.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Moves {
+    /// North, east, south and west.
+    Four,
+    /// Diagonals too, but never cutting a corner past a blocked cell.
+    Eight,
+}
+
+/// An estimate of the cost from one cell to another. To find the cheapest
+/// path, A* needs it never to overestimate.
+pub trait Heuristic {
+    fn estimate(&self, from: (i32, i32), to: (i32, i32)) -> u64;
+}
+
+/// Any closure of the right shape is a heuristic.
+impl<F> Heuristic for F
+    where F: Fn((i32, i32), (i32, i32)) -> u64
+{
+    fn estimate(&self, from: (i32, i32), to: (i32, i32)) -> u64 {
+        self(from, to)
+    }
+}
+
+/// So heuristics chosen at run time can be passed to `a_star`.
+impl Heuristic for &dyn Heuristic {
+    fn estimate(&self, from: (i32, i32), to: (i32, i32)) -> u64 {
+        (**self).estimate(from, to)
+    }
+}
+
+/// No estimate at all, which turns A* into Dijkstra's algorithm.
+#[derive(Clone, Copy, Debug)]
+pub struct Zero;
+
+impl Heuristic for Zero {
+    fn estimate(&self, _: (i32, i32), _: (i32, i32)) -> u64 {
+        0
+    }
+}
+
+/// The right estimate for `Moves::Four`.
+#[derive(Clone, Copy, Debug)]
+pub struct Manhattan;
+
+impl Heuristic for Manhattan {
+    fn estimate(&self, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> u64 {
+        (x1.abs_diff(x0) as u64 + y1.abs_diff(y0) as u64) * STRAIGHT
+    }
+}
+
+/// The right estimate for `Moves::Eight`: diagonal steps as far as they
+/// help, then straight ones.
+#[derive(Clone, Copy, Debug)]
+pub struct Octile;
+
+impl Heuristic for Octile {
+    fn estimate(&self, (x0, y0): (i32, i32), (x1, y1): (i32, i32)) -> u64 {
+        let (dx, dy) = (x1.abs_diff(x0) as u64, y1.abs_diff(y0) as u64);
+        let (long, short) = (dx.max(dy), dx.min(dy));
+        (long - short) * STRAIGHT + short * DIAGONAL
+    }
+}
+
+/// A route from one cell to another.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Path {
+    pub steps: Vec<Direction>,
+    /// The total cost of the steps, in the units of `STRAIGHT` and
+    /// `DIAGONAL`.
+    pub cost: u64,
+}
+
+/// A `width` by `height` map of terrain. Each cell has a cost to step onto,
+/// at least 1, or is impassable.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid {
+    width: i32,
+    height: i32,
+    costs: Vec<Option<u32>>,
+}
+
+impl Grid {
+    /// A grid where every cell costs 1. Panics if either size is negative,
+    /// or if there would be more cells than an `i32` can count.
+    pub fn new(width: i32, height: i32) -> Grid {
+        assert!(width >= 0 && height >= 0, "grid size can't be negative");
+        let cells = width.checked_mul(height).expect("grid is too big");
+        Grid { width, height, costs: vec![Some(1); cells as usize] }
+    }
+
+    /// A grid the size of `world`'s map, with every obstacle, and every
+    /// creature except `traveller`, blocking the cells it occupies. With a
+    /// `traveller`, the grid is then the one its whole body sees, as
+    /// `for_footprint` describes.
+    pub fn from_world(world: &World, traveller: Option<CreatureId>) -> Grid {
+        let mut grid = Grid::new(world.width(), world.height());
+        for (_, obstacle) in world.obstacles() {
+            grid.block_visible(obstacle);
+        }
+        for (id, creature) in world.creatures() {
+            if Some(id) != traveller {
+                grid.block_visible(creature);
+            }
+        }
+        match traveller.and_then(|id| world.creature(id)) {
+            Some(me) => grid.for_footprint(&footprint(me)),
+            None => grid,
+        }
+    }
+
+    /// The grid as seen by something whose body covers `footprint`, given
+    /// as offsets from its position: (x, y) is passable only if every cell
+    /// of the body would be, standing there. Body cells off the grid don't
+    /// count, as in a `World`, where only a creature's position has to stay
+    /// on the map. Each cell keeps its own cost.
+    pub fn for_footprint(&self, footprint: &[(i32, i32)]) -> Grid {
+        let mut grid = self.clone();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let blocked = footprint.iter().any(|&(dx, dy)| {
+                    self.in_bounds(x + dx, y + dy) && !self.passable(x + dx, y + dy)
+                });
+                if blocked {
+                    grid.block(x, y);
+                }
+            }
+        }
+        grid
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn in_bounds(&self, x: i32, y: i32) -> bool {
+        0 <= x && x < self.width && 0 <= y && y < self.height
+    }
+
+    fn index(&self, x: i32, y: i32) -> usize {
+        (y * self.width + x) as usize
+    }
+
+    /// The cost of stepping onto (x, y), or `None` if it's blocked or off
+    /// the grid.
+    pub fn cost(&self, x: i32, y: i32) -> Option<u32> {
+        if self.in_bounds(x, y) {
+            self.costs[self.index(x, y)]
+        } else {
+            None
+        }
+    }
+
+    pub fn passable(&self, x: i32, y: i32) -> bool {
+        self.cost(x, y).is_some()
+    }
+
+    /// Set a cell's cost, or block it with `None`. Any cost up to
+    /// `u32::MAX` works; panics on a cost of 0, which would make the
+    /// heuristics overestimate.
+    pub fn set_cost(&mut self, x: i32, y: i32, cost: Option<u32>) {
+        assert!(cost != Some(0), "terrain must cost at least 1");
+        if self.in_bounds(x, y) {
+            let i = self.index(x, y);
+            self.costs[i] = cost;
+        }
+    }
+
+    pub fn block(&mut self, x: i32, y: i32) {
+        self.set_cost(x, y, None);
+    }
+
+    /// Block every cell `visible` hit-tests true for.
+    pub fn block_visible(&mut self, visible: &dyn Visible) {
+        let b = visible.bounds();
+        for y in b.min.y() ..= b.max.y() {
+            for x in b.min.x() ..= b.max.x() {
+                if visible.hit_test(x, y) {
+                    self.block(x, y);
+                }
+            }
+        }
+    }
+
+    /// The steps allowed from (x, y), with their costs.
+    fn neighbours(&self, (x, y): (i32, i32), moves: Moves) -> Vec<(Direction, (i32, i32), u64)> {
+        let directions: &[Direction] = match moves {
+            Moves::Four => &Direction::CARDINAL,
+            Moves::Eight => &Direction::ALL,
+        };
+        directions.iter().filter_map(|&d| {
+            let (dx, dy) = d.delta();
+            let cost = u64::from(self.cost(x + dx, y + dy)?);
+            if d.is_diagonal() {
+                if !self.passable(x + dx, y) || !self.passable(x, y + dy) {
+                    return None;
+                }
+                Some((d, (x + dx, y + dy), cost * DIAGONAL))
+            } else {
+                Some((d, (x + dx, y + dy), cost * STRAIGHT))
+            }
+        }).collect()
+    }
+
+    /// Follow the `came_from` links back from `to`.
+    fn trace(&self, came_from: &[Option<(Direction, u64)>], from: (i32, i32), to: (i32, i32)) -> Path {
+        let mut steps = vec![];
+        let mut cost = 0;
+        let mut at = to;
+        while at != from {
+            let (d, c) = came_from[self.index(at.0, at.1)].unwrap();
+            steps.push(d);
+            cost += c;
+            let (dx, dy) = d.delta();
+            at = (at.0 - dx, at.1 - dy);
+        }
+        steps.reverse();
+        Path { steps, cost }
+    }
+
+    /// The path with the fewest steps, ignoring terrain costs (though the
+    /// path's `cost` counts them).
+    pub fn bfs(&self, from: (i32, i32), to: (i32, i32), moves: Moves) -> Option<Path> {
+        if !self.passable(from.0, from.1) || !self.passable(to.0, to.1) {
+            return None;
+        }
+        let mut came_from = vec![None; self.costs.len()];
+        let mut seen = vec![false; self.costs.len()];
+        seen[self.index(from.0, from.1)] = true;
+        let mut queue = VecDeque::from(vec![from]);
+        while let Some(at) = queue.pop_front() {
+            if at == to {
+                return Some(self.trace(&came_from, from, to));
+            }
+            for (d, next, cost) in self.neighbours(at, moves) {
+                let i = self.index(next.0, next.1);
+                if !seen[i] {
+                    seen[i] = true;
+                    came_from[i] = Some((d, cost));
+                    queue.push_back(next);
+                }
+            }
+        }
+        None
+    }
+
+    /// The cheapest path, by Dijkstra's algorithm.
+    pub fn dijkstra(&self, from: (i32, i32), to: (i32, i32), moves: Moves) -> Option<Path> {
+        self.a_star(from, to, moves, Zero)
+    }
+
+    /// The cheapest path, by A* guided by `heuristic`. If the heuristic
+    /// overestimates, the path found may not be the cheapest.
+    pub fn a_star<H: Heuristic>(&self, from: (i32, i32), to: (i32, i32), moves: Moves, heuristic: H)
+        -> Option<Path>
+    {
+        if !self.passable(from.0, from.1) || !self.passable(to.0, to.1) {
+            return None;
+        }
+        let mut best = vec![u64::MAX; self.costs.len()];
+        let mut came_from = vec![None; self.costs.len()];
+        best[self.index(from.0, from.1)] = 0;
+        // Ordered by estimated total cost, then by cost so far.
+        let mut open = BinaryHeap::new();
+        open.push(Reverse((heuristic.estimate(from, to), 0, from)));
+
+        while let Some(Reverse((_, cost, at))) = open.pop() {
+            if at == to {
+                return Some(self.trace(&came_from, from, to));
+            }
+            if cost > best[self.index(at.0, at.1)] {
+                continue; // a stale entry; `at` was reached more cheaply since
+            }
+            for (d, next, step) in self.neighbours(at, moves) {
+                let i = self.index(next.0, next.1);
+                let total = cost + step;
+                if total < best[i] {
+                    best[i] = total;
+                    came_from[i] = Some((d, step));
+                    open.push(Reverse((total + heuristic.estimate(next, to), total, next)));
+                }
+            }
+        }
+        None
+    }
+}
+
+/// The cells `creature` occupies, as offsets from its position.
+fn footprint(creature: &dyn Creature) -> Vec<(i32, i32)> {
+    let (x, y) = creature.position();
+    let b = creature.bounds();
+    let mut cells = vec![];
+    for cy in b.min.y() ..= b.max.y() {
+        for cx in b.min.x() ..= b.max.x() {
+            if creature.hit_test(cx, cy) {
+                cells.push((cx - x, cy - y));
+            }
+        }
+    }
+    cells
+}
+
+impl World {
+    /// A path for `id` to (x, y) around everything else in the world, with
+    /// room for the whole of `id`'s body at every step.
+    pub fn find_path(&self, id: CreatureId, to: (i32, i32), moves: Moves) -> Option<Path> {
+        let me = self.creature(id)?;
+        let grid = Grid::from_world(self, Some(id));
+        match moves {
+            Moves::Four => grid.a_star(me.position(), to, moves, Manhattan),
+            Moves::Eight => grid.a_star(me.position(), to, moves, Octile),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Broom;
+    use crate::rand::{Rng, XorShiftRng};
+
+    /// Walk `path` from `from`, checking every step is allowed, and return
+    /// where it ends and what it cost.
+    fn walk(grid: &Grid, from: (i32, i32), path: &Path, moves: Moves) -> ((i32, i32), u64) {
+        let mut at = from;
+        let mut cost = 0;
+        for &d in &path.steps {
+            let (_, next, c) = grid.neighbours(at, moves).into_iter()
+                .find(|&(step, _, _)| step == d)
+                .expect("illegal step");
+            at = next;
+            cost += c;
+        }
+        (at, cost)
+    }
+
+    #[test]
+    fn test_random_grids() {
+        let mut rng = XorShiftRng::seed_from_u64(47);
+        for _ in 0..50 {
+            let mut grid = Grid::new(16, 12);
+            for y in 0..12 {
+                for x in 0..16 {
+                    match rng.below(10) {
+                        0 | 1 => grid.block(x, y),
+                        2 => grid.set_cost(x, y, Some(5)),
+                        _ => {}
+                    }
+                }
+            }
+            let from = (rng.below(16) as i32, rng.below(12) as i32);
+            let to = (rng.below(16) as i32, rng.below(12) as i32);
+
+            for (moves, heuristic) in [(Moves::Four, &Manhattan as &dyn Heuristic),
+                                       (Moves::Eight, &Octile)] {
+                let cheapest = grid.dijkstra(from, to, moves);
+                let guided = grid.a_star(from, to, moves, heuristic);
+                let fewest = grid.bfs(from, to, moves);
+                assert_eq!(cheapest.is_some(), guided.is_some());
+                assert_eq!(cheapest.is_some(), fewest.is_some());
+                if let (Some(c), Some(g), Some(f)) = (cheapest, guided, fewest) {
+                    assert_eq!(walk(&grid, from, &c, moves), (to, c.cost));
+                    assert_eq!(walk(&grid, from, &g, moves), (to, g.cost));
+                    assert_eq!(walk(&grid, from, &f, moves), (to, f.cost));
+                    assert_eq!(g.cost, c.cost);
+                    assert!(f.cost >= c.cost);
+                    assert!(f.steps.len() <= c.steps.len());
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_around_obstacles() {
+        let mut world = World::new(7, 5, 0);
+        world.add_obstacle(Broom::new(3, 3, 1)); // a wall at x = 3, y = 1 ..= 3
+        let broom = world.add_creature(Broom::new(1, 2, 0), 1.0);
+
+        // The broom's handle won't fit under the wall, so it goes over the
+        // top in eight steps.
+        let path = world.find_path(broom, (5, 2), Moves::Four).unwrap();
+        assert_eq!(path.steps.len(), 8);
+        assert_eq!(path.cost, 8 * STRAIGHT);
+
+        let path = world.find_path(broom, (5, 2), Moves::Eight).unwrap();
+        assert_eq!(path.steps.len(), 6);
+        assert_eq!(path.cost, 2 * DIAGONAL + 4 * STRAIGHT);
+
+        // Something a cell high could go under as easily, until the bottom
+        // row is made boggy.
+        let mut grid = Grid::new(7, 5);
+        grid.block_visible(&Broom::new(3, 3, 1));
+        assert_eq!(grid.dijkstra((1, 2), (5, 2), Moves::Four).unwrap().cost, 8 * STRAIGHT);
+        for x in 0..7 {
+            grid.set_cost(x, 4, Some(9));
+        }
+        let path = grid.a_star((1, 2), (5, 2), Moves::Four, |a, b| Manhattan.estimate(a, b)).unwrap();
+        assert_eq!(path.cost, 8 * STRAIGHT);
+        let mut y = 2;
+        for step in &path.steps {
+            y += step.delta().1;
+            assert!(y < 4);
+        }
+
+        // Too tall to pass under a ledge, a broom has to duck down a row.
+        let mut world = World::new(10, 10, 0);
+        world.add_obstacle(Broom::new(5, 4, 0)); // a ledge at x = 5, y = 3 ..= 4
+        let broom = world.add_creature(Broom::new(1, 8, 3), 10.0);
+        let path = world.find_path(broom, (8, 8), Moves::Four).unwrap();
+        assert_eq!(path.steps.len(), 9);
+        for &d in &path.steps {
+            assert!(world.can_move(broom, d));
+            world.turn(broom, d);
+            assert_eq!(world.tick(), []);
+        }
+        assert_eq!(world.creature(broom).unwrap().position(), (8, 8));
+
+        // Even the dearest terrain can't overflow a path's cost.
+        let mut grid = Grid::new(3, 1);
+        grid.set_cost(1, 0, Some(u32::MAX));
+        grid.set_cost(2, 0, Some(u32::MAX));
+        let path = grid.dijkstra((0, 0), (2, 0), Moves::Four).unwrap();
+        assert_eq!(path.cost, 2 * u64::from(u32::MAX) * STRAIGHT);
+    }
+}
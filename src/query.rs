@@ -0,0 +1,728 @@
+//! A small SQL-like language that compiles to the built-in `Mapper` and
+//! `Reducer` implementations and runs through `run_query`:
+//!
+//! ```text
+//! SELECT key, count(*), avg(price) FROM data WHERE price > 10 AND NOT sold GROUP BY key
+//! ```
+
+use std::error::Error;
+use std::fmt;
+
+use crate::mapreduce::{run_query, Aggregate, AggregateFn, DataSet, Key, Mapper, Reducer, Row, Value};
+
+/// A parse or compile error, located at a 1-based column of the query text.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QueryError {
+    pub column: usize,
+    pub message: String,
+}
+
+impl QueryError {
+    fn new(column: usize, message: String) -> QueryError {
+        QueryError { column, message }
+    }
+
+    /// Render the query with a caret under the offending column.
+    pub fn pointer(&self, source: &str) -> String {
+        format!("{}\n{}^ {}", source, " ".repeat(self.column - 1), self.message)
+    }
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+impl Error for QueryError {}
+
+pub type QueryResult<T> = Result<T, QueryError>;
+
+#[derive(Clone, Debug, PartialEq)]
+enum Tok {
+    Ident(String),
+    Literal(Value),
+    Symbol(&'static str),
+    End,
+}
+
+impl fmt::Display for Tok {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tok::Ident(s) => write!(f, "`{}`", s),
+            Tok::Literal(Value::Str(s)) => write!(f, "'{}'", s),
+            Tok::Literal(v) => write!(f, "`{}`", v),
+            Tok::Symbol(s) => write!(f, "`{}`", s),
+            Tok::End => write!(f, "end of query"),
+        }
+    }
+}
+
+/// Split `text` into tokens, each paired with its 1-based column.
+fn tokenize(text: &str) -> QueryResult<Vec<(Tok, usize)>> {
+    const SYMBOLS: [&str; 12] = ["<=", ">=", "!=", "<>", "=", "<", ">",
+                                 "(", ")", ",", "*", ";"];
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    'outer: while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Tok::Ident(chars[start..i].iter().collect()), column));
+        } else if c.is_ascii_digit()
+            || (c == '-' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let digits: String = chars[start..i].iter().collect();
+            let value = if digits.contains('.') {
+                digits.parse().map(Value::Float).ok()
+            } else {
+                digits.parse().map(Value::Int).ok()
+            };
+            match value {
+                Some(v) => tokens.push((Tok::Literal(v), column)),
+                None => return Err(QueryError::new(column,
+                                                   format!("bad number `{}`", digits))),
+            }
+        } else if c == '\'' {
+            // Strings are single-quoted; a doubled quote stands for one.
+            let mut s = String::new();
+            i += 1;
+            loop {
+                match chars.get(i) {
+                    None => return Err(QueryError::new(column,
+                                                       "unterminated string".to_string())),
+                    Some('\'') if chars.get(i + 1) == Some(&'\'') => {
+                        s.push('\'');
+                        i += 2;
+                    }
+                    Some('\'') => {
+                        i += 1;
+                        break;
+                    }
+                    Some(&ch) => {
+                        s.push(ch);
+                        i += 1;
+                    }
+                }
+            }
+            tokens.push((Tok::Literal(Value::Str(s)), column));
+        } else {
+            for sym in SYMBOLS.iter() {
+                let len = sym.len();
+                if i + len <= chars.len()
+                    && chars[i..i + len].iter().copied().eq(sym.chars())
+                {
+                    tokens.push((Tok::Symbol(sym), column));
+                    i += len;
+                    continue 'outer;
+                }
+            }
+            return Err(QueryError::new(column, format!("unexpected character `{}`", c)));
+        }
+    }
+    tokens.push((Tok::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+/// A name as it appeared in the query, with its column for error reporting.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Name {
+    pub text: String,
+    pub column: usize,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SelectItem {
+    Column(Name),
+    /// `func(arg)`, or `func(*)` when `arg` is `None`.
+    Aggregate { func: AggregateFn, arg: Option<Name>, column: usize },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CompareOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operand {
+    Column(Name),
+    Literal(Value),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expr {
+    Compare(Operand, CompareOp, Operand),
+    /// A bare column, true when the column holds `true`.
+    Truthy(Name),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+/// A parsed, but not yet compiled, query.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Query {
+    pub select: Vec<SelectItem>,
+    pub from: Name,
+    pub filter: Option<Expr>,
+    pub group_by: Vec<Name>,
+}
+
+struct Parser {
+    tokens: Vec<(Tok, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Tok {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Tok, usize) {
+        let tok = self.tokens[self.pos].clone();
+        if self.pos + 1 < self.tokens.len() {
+            self.pos += 1;
+        }
+        tok
+    }
+
+    fn unexpected<T>(&self, wanted: &str) -> QueryResult<T> {
+        Err(QueryError::new(self.column(),
+                            format!("expected {}, found {}", wanted, self.peek())))
+    }
+
+    fn is_keyword(&self, keyword: &str) -> bool {
+        match self.peek() {
+            Tok::Ident(s) => s.eq_ignore_ascii_case(keyword),
+            _ => false,
+        }
+    }
+
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        if self.is_keyword(keyword) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> QueryResult<()> {
+        if self.eat_keyword(keyword) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", keyword))
+        }
+    }
+
+    fn eat_symbol(&mut self, symbol: &'static str) -> bool {
+        if *self.peek() == Tok::Symbol(symbol) {
+            self.next();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect_symbol(&mut self, symbol: &'static str) -> QueryResult<()> {
+        if self.eat_symbol(symbol) {
+            Ok(())
+        } else {
+            self.unexpected(&format!("`{}`", symbol))
+        }
+    }
+
+    fn name(&mut self, what: &str) -> QueryResult<Name> {
+        match self.peek().clone() {
+            Tok::Ident(text) if !is_reserved(&text) => {
+                let column = self.column();
+                self.next();
+                Ok(Name { text, column })
+            }
+            _ => self.unexpected(what),
+        }
+    }
+
+    fn query(&mut self) -> QueryResult<Query> {
+        self.expect_keyword("select")?;
+        let mut select = vec![self.select_item()?];
+        while self.eat_symbol(",") {
+            select.push(self.select_item()?);
+        }
+
+        self.expect_keyword("from")?;
+        let from = self.name("a data set name")?;
+
+        let filter = if self.eat_keyword("where") {
+            Some(self.or_expr()?)
+        } else {
+            None
+        };
+
+        let mut group_by = vec![];
+        if self.eat_keyword("group") {
+            self.expect_keyword("by")?;
+            group_by.push(self.name("a column name")?);
+            while self.eat_symbol(",") {
+                group_by.push(self.name("a column name")?);
+            }
+        }
+
+        self.eat_symbol(";");
+        if *self.peek() != Tok::End {
+            return self.unexpected("end of query");
+        }
+        Ok(Query { select, from, filter, group_by })
+    }
+
+    fn select_item(&mut self) -> QueryResult<SelectItem> {
+        let name = self.name("a column or aggregate")?;
+        if !self.eat_symbol("(") {
+            return Ok(SelectItem::Column(name));
+        }
+
+        let func = match AggregateFn::from_name(&name.text) {
+            Some(func) => func,
+            None => return Err(QueryError::new(
+                name.column, format!("unknown aggregate function `{}`", name.text))),
+        };
+        let arg = if self.eat_symbol("*") {
+            if func != AggregateFn::Count {
+                return Err(QueryError::new(
+                    name.column, format!("only count accepts `*`, not {}", name.text)));
+            }
+            None
+        } else {
+            Some(self.name("a column name or `*`")?)
+        };
+        self.expect_symbol(")")?;
+        Ok(SelectItem::Aggregate { func, arg, column: name.column })
+    }
+
+    fn or_expr(&mut self) -> QueryResult<Expr> {
+        let mut lhs = self.and_expr()?;
+        while self.eat_keyword("or") {
+            let rhs = self.and_expr()?;
+            lhs = Expr::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn and_expr(&mut self) -> QueryResult<Expr> {
+        let mut lhs = self.not_expr()?;
+        while self.eat_keyword("and") {
+            let rhs = self.not_expr()?;
+            lhs = Expr::And(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    fn not_expr(&mut self) -> QueryResult<Expr> {
+        if self.eat_keyword("not") {
+            return Ok(Expr::Not(Box::new(self.not_expr()?)));
+        }
+        if self.eat_symbol("(") {
+            let inner = self.or_expr()?;
+            self.expect_symbol(")")?;
+            return Ok(inner);
+        }
+
+        let lhs = self.operand()?;
+        let op = match self.peek() {
+            Tok::Symbol("=") => CompareOp::Eq,
+            Tok::Symbol("!=") | Tok::Symbol("<>") => CompareOp::Ne,
+            Tok::Symbol("<") => CompareOp::Lt,
+            Tok::Symbol("<=") => CompareOp::Le,
+            Tok::Symbol(">") => CompareOp::Gt,
+            Tok::Symbol(">=") => CompareOp::Ge,
+            _ => {
+                return match lhs {
+                    Operand::Column(name) => Ok(Expr::Truthy(name)),
+                    Operand::Literal(_) => self.unexpected("a comparison operator"),
+                };
+            }
+        };
+        self.next();
+        let rhs = self.operand()?;
+        Ok(Expr::Compare(lhs, op, rhs))
+    }
+
+    fn operand(&mut self) -> QueryResult<Operand> {
+        let column = self.column();
+        match self.peek().clone() {
+            Tok::Literal(v) => {
+                self.next();
+                Ok(Operand::Literal(v))
+            }
+            Tok::Ident(s) if s.eq_ignore_ascii_case("true") => {
+                self.next();
+                Ok(Operand::Literal(Value::Bool(true)))
+            }
+            Tok::Ident(s) if s.eq_ignore_ascii_case("false") => {
+                self.next();
+                Ok(Operand::Literal(Value::Bool(false)))
+            }
+            Tok::Ident(s) if s.eq_ignore_ascii_case("null") => {
+                self.next();
+                Ok(Operand::Literal(Value::Null))
+            }
+            Tok::Ident(text) if !is_reserved(&text) => {
+                self.next();
+                Ok(Operand::Column(Name { text, column }))
+            }
+            _ => self.unexpected("a column name or value"),
+        }
+    }
+}
+
+fn is_reserved(word: &str) -> bool {
+    const RESERVED: [&str; 11] = ["select", "from", "where", "group", "by", "and",
+                                  "or", "not", "true", "false", "null"];
+    RESERVED.iter().any(|r| word.eq_ignore_ascii_case(r))
+}
+
+/// Parse query text.
+pub fn parse(text: &str) -> QueryResult<Query> {
+    let mut parser = Parser { tokens: tokenize(text)?, pos: 0 };
+    parser.query()
+}
+
+#[derive(Clone, Debug)]
+enum Arg {
+    Column(usize),
+    Literal(Value),
+}
+
+impl Arg {
+    fn get<'a>(&'a self, row: &'a [Value]) -> &'a Value {
+        match self {
+            Arg::Column(i) => &row[*i],
+            Arg::Literal(v) => v,
+        }
+    }
+}
+
+/// A `WHERE` clause with its columns resolved to positions.
+#[derive(Clone, Debug)]
+enum Filter {
+    Compare(Arg, CompareOp, Arg),
+    Truthy(usize),
+    Not(Box<Filter>),
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+}
+
+impl Filter {
+    fn matches(&self, row: &[Value]) -> bool {
+        match self {
+            Filter::Compare(lhs, op, rhs) => {
+                let (a, b) = (lhs.get(row), rhs.get(row));
+                // As in SQL, comparing against null is never true.
+                if let (Value::Null, _) | (_, Value::Null) = (a, b) {
+                    return false;
+                }
+                match op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    CompareOp::Lt => a < b,
+                    CompareOp::Le => a <= b,
+                    CompareOp::Gt => a > b,
+                    CompareOp::Ge => a >= b,
+                }
+            }
+            Filter::Truthy(i) => row[*i] == Value::Bool(true),
+            Filter::Not(f) => !f.matches(row),
+            Filter::And(a, b) => a.matches(row) && b.matches(row),
+            Filter::Or(a, b) => a.matches(row) || b.matches(row),
+        }
+    }
+}
+
+/// The built-in `Mapper` a query compiles to: drop rows failing the filter,
+/// key the rest on the `GROUP BY` columns, and pass along the aggregates'
+/// input columns.
+#[derive(Clone, Debug)]
+pub struct QueryMapper {
+    filter: Option<Filter>,
+    key_columns: Vec<usize>,
+    input_columns: Vec<usize>,
+}
+
+impl Mapper for QueryMapper {
+    fn map(&self, row: &[Value], emit: &mut dyn FnMut(Key, Row)) {
+        if let Some(filter) = &self.filter {
+            if !filter.matches(row) {
+                return;
+            }
+        }
+        let key = self.key_columns.iter().map(|&i| row[i].clone()).collect();
+        let values = self.input_columns.iter().map(|&i| row[i].clone()).collect();
+        emit(key, values);
+    }
+}
+
+/// Where each output column comes from.
+#[derive(Clone, Copy, Debug)]
+enum Output {
+    Key(usize),
+    Aggregate(usize),
+}
+
+/// A query compiled against a particular data set's columns.
+#[derive(Clone, Debug)]
+pub struct Plan {
+    pub mapper: QueryMapper,
+    pub reducer: Vec<Aggregate>,
+    columns: Vec<String>,
+    outputs: Vec<Output>,
+}
+
+/// The rows a query produced, with a header naming each column.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Table {
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", self.columns.join("\t"))?;
+        for row in &self.rows {
+            let cells: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            writeln!(f, "{}", cells.join("\t"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Query {
+    /// Resolve column names against `data` and build the mapper and reducer.
+    pub fn compile(&self, data: &DataSet) -> QueryResult<Plan> {
+        if self.from.text != data.name {
+            return Err(QueryError::new(
+                self.from.column,
+                format!("unknown data set `{}`; expected `{}`", self.from.text, data.name)));
+        }
+
+        let resolve = |name: &Name| {
+            data.column_index(&name.text).ok_or_else(|| QueryError::new(
+                name.column, format!("no column named `{}` in `{}`", name.text, data.name)))
+        };
+
+        let mut key_columns = vec![];
+        for name in &self.group_by {
+            key_columns.push(resolve(name)?);
+        }
+
+        let has_aggregates = self.select.iter()
+            .any(|item| matches!(item, SelectItem::Aggregate { .. }));
+        let mut input_columns = vec![];
+        let mut reducer = vec![];
+        let mut columns = vec![];
+        let mut outputs = vec![];
+        for item in &self.select {
+            match item {
+                SelectItem::Column(name) => {
+                    let index = resolve(name)?;
+                    let position = match key_columns.iter().position(|&k| k == index) {
+                        Some(p) => p,
+                        None if has_aggregates || !self.group_by.is_empty() => {
+                            return Err(QueryError::new(name.column, format!(
+                                "`{}` must appear in GROUP BY or inside an aggregate",
+                                name.text)));
+                        }
+                        None => {
+                            return Err(QueryError::new(name.column,
+                                "queries must aggregate or use GROUP BY".to_string()));
+                        }
+                    };
+                    columns.push(name.text.clone());
+                    outputs.push(Output::Key(position));
+                }
+                SelectItem::Aggregate { func, arg, .. } => {
+                    let input = match arg {
+                        Some(name) => {
+                            let index = resolve(name)?;
+                            if let AggregateFn::Sum | AggregateFn::Avg = func {
+                                check_numeric(data, index, *func, name)?;
+                            }
+                            input_columns.push(index);
+                            Some(input_columns.len() - 1)
+                        }
+                        None => None,
+                    };
+                    let label = match arg {
+                        Some(name) => format!("{:?}({})", func, name.text),
+                        None => format!("{:?}(*)", func),
+                    };
+                    columns.push(label.to_lowercase());
+                    outputs.push(Output::Aggregate(reducer.len()));
+                    reducer.push(Aggregate { func: *func, input });
+                }
+            }
+        }
+
+        let filter = match &self.filter {
+            Some(expr) => Some(compile_expr(expr, &resolve)?),
+            None => None,
+        };
+
+        Ok(Plan {
+            mapper: QueryMapper { filter, key_columns, input_columns },
+            reducer,
+            columns,
+            outputs,
+        })
+    }
+}
+
+/// Sums and averages need numbers: fail if column `index` holds anything
+/// but numbers and nulls.
+fn check_numeric(data: &DataSet, index: usize, func: AggregateFn, name: &Name) -> QueryResult<()> {
+    let mut values = data.rows().map(|row| &row[index]);
+    match values.find(|v| !matches!(v, Value::Null) && v.as_f64().is_none()) {
+        Some(value) => Err(QueryError::new(name.column, format!(
+            "{} needs a numeric column, but `{}` holds `{}`",
+            format!("{:?}", func).to_lowercase(), name.text, value))),
+        None => Ok(()),
+    }
+}
+
+fn compile_expr<F>(expr: &Expr, resolve: &F) -> QueryResult<Filter>
+    where F: Fn(&Name) -> QueryResult<usize>
+{
+    let arg = |operand: &Operand| -> QueryResult<Arg> {
+        match operand {
+            Operand::Column(name) => Ok(Arg::Column(resolve(name)?)),
+            Operand::Literal(v) => Ok(Arg::Literal(v.clone())),
+        }
+    };
+    Ok(match expr {
+        Expr::Compare(lhs, op, rhs) => Filter::Compare(arg(lhs)?, *op, arg(rhs)?),
+        Expr::Truthy(name) => Filter::Truthy(resolve(name)?),
+        Expr::Not(e) => Filter::Not(Box::new(compile_expr(e, resolve)?)),
+        Expr::And(a, b) => Filter::And(Box::new(compile_expr(a, resolve)?),
+                                       Box::new(compile_expr(b, resolve)?)),
+        Expr::Or(a, b) => Filter::Or(Box::new(compile_expr(a, resolve)?),
+                                     Box::new(compile_expr(b, resolve)?)),
+    })
+}
+
+impl Plan {
+    /// Run the compiled query through `run_query`.
+    pub fn execute(&self, data: &DataSet) -> Table {
+        let mut results = run_query(data, &self.mapper, &self.reducer);
+        // Without GROUP BY there's always exactly one row, even with no
+        // input: `count(*)` of nothing is 0.
+        if results.is_empty() && self.mapper.key_columns.is_empty() {
+            results.push((vec![], self.reducer.finish(self.reducer.start())));
+        }
+        let rows = results.into_iter()
+            .map(|(key, aggregates)| {
+                self.outputs.iter()
+                    .map(|out| match *out {
+                        Output::Key(i) => key[i].clone(),
+                        Output::Aggregate(i) => aggregates[i].clone(),
+                    })
+                    .collect()
+            })
+            .collect();
+        Table { columns: self.columns.clone(), rows }
+    }
+}
+
+/// Parse, compile and run `text` against `data`.
+pub fn execute(text: &str, data: &DataSet) -> QueryResult<Table> {
+    Ok(parse(text)?.compile(data)?.execute(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sales() -> DataSet {
+        let mut data = DataSet::new("data", &["key", "price", "sold"]);
+        data.add_partition(vec![
+            vec!["apple".into(), 12.into(), true.into()],
+            vec!["pear".into(), 8.into(), true.into()],
+        ]);
+        data.add_partition(vec![
+            vec!["apple".into(), 20.into(), false.into()],
+            vec!["pear".into(), 15.into(), true.into()],
+            vec!["fig".into(), Value::Null, true.into()],
+        ]);
+        data
+    }
+
+    #[test]
+    fn test_group_by_with_filter() {
+        let table = execute("SELECT key, count(*), sum(price) FROM data \
+                             WHERE sold AND (price >= 10 OR key = 'fig') GROUP BY key",
+                            &sales()).unwrap();
+        assert_eq!(table.columns, vec!["key", "count(*)", "sum(price)"]);
+        assert_eq!(table.rows, vec![
+            vec!["apple".into(), 1.into(), 12.into()],
+            vec!["fig".into(), 1.into(), Value::Null],
+            vec!["pear".into(), 1.into(), 15.into()],
+        ]);
+    }
+
+    #[test]
+    fn test_errors_point_at_column() {
+        let err = execute("SELECT key, count(*) FROM data GROUP BY kye", &sales())
+            .unwrap_err();
+        assert_eq!(err.column, 41);
+        assert_eq!(err.message, "no column named `kye` in `data`");
+
+        let err = parse("SELECT key FROM data WHERE price >").unwrap_err();
+        assert_eq!(err.column, 35);
+        assert_eq!(err.message, "expected a column name or value, found end of query");
+
+        let err = execute("SELECT avg(key) FROM data", &sales()).unwrap_err();
+        assert_eq!(err.column, 12);
+        assert_eq!(err.message, "avg needs a numeric column, but `key` holds `apple`");
+    }
+
+    #[test]
+    fn test_ungrouped_aggregates() {
+        let mut empty = DataSet::new("data", &["key", "price", "sold"]);
+        let table = execute("SELECT count(*), sum(price) FROM data", &empty).unwrap();
+        assert_eq!(table.rows, vec![vec![0.into(), Value::Null]]);
+        empty.add_partition(vec![]);
+        let table = execute("SELECT count(*) FROM data WHERE sold", &empty).unwrap();
+        assert_eq!(table.rows, vec![vec![0.into()]]);
+
+        // Sums stay exact while they fit in an Int, and go over to floats
+        // past that rather than wrapping.
+        let mut big = DataSet::new("data", &["n"]);
+        big.add_partition(vec![vec![i64::MAX.into()], vec![(-1).into()]]);
+        let table = execute("SELECT sum(n) FROM data", &big).unwrap();
+        assert!(matches!(table.rows[0][0], Value::Int(n) if n == i64::MAX - 1));
+        big.add_partition(vec![vec![2.into()]]);
+        let table = execute("SELECT sum(n), avg(n) FROM data", &big).unwrap();
+        assert!(matches!(table.rows[0][0], Value::Float(x) if x == i64::MAX as f64));
+        assert!(matches!(table.rows[0][1], Value::Float(_)));
+    }
+}
@@ -0,0 +1,117 @@
+//! The buddy traits from main.rs, `Rng` and `Rand`, with a small seeded
+//! generator so anything random in the crate can be replayed.
+
+/// A random number generator.
+pub trait Rng {
+    fn next_u32(&mut self) -> u32;
+
+    fn next_u64(&mut self) -> u64 {
+        (u64::from(self.next_u32()) << 32) | u64::from(self.next_u32())
+    }
+
+    /// A uniformly distributed integer in `0 .. n`. Panics if `n` is zero.
+    fn below(&mut self, n: u64) -> u64 {
+        assert!(n > 0, "can't pick from an empty range");
+        // Reject the incomplete final block of u64 values so every result
+        // is equally likely.
+        let zone = u64::MAX - u64::MAX % n;
+        loop {
+            let x = self.next_u64();
+            if x < zone {
+                return x % n;
+            }
+        }
+    }
+}
+
+impl<R: Rng + ?Sized> Rng for &mut R {
+    fn next_u32(&mut self) -> u32 {
+        (**self).next_u32()
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        (**self).next_u64()
+    }
+}
+
+/// A type that can be randomly generated using an `Rng`.
+pub trait Rand: Sized {
+    fn rand<R: Rng>(rng: &mut R) -> Self;
+}
+
+impl Rand for u32 {
+    fn rand<R: Rng>(rng: &mut R) -> u32 {
+        rng.next_u32()
+    }
+}
+
+impl Rand for u64 {
+    fn rand<R: Rng>(rng: &mut R) -> u64 {
+        rng.next_u64()
+    }
+}
+
+impl Rand for bool {
+    fn rand<R: Rng>(rng: &mut R) -> bool {
+        rng.next_u32() & 1 == 1
+    }
+}
+
+/// A number, 0.0 <= x < 1.0
+impl Rand for f64 {
+    fn rand<R: Rng>(rng: &mut R) -> f64 {
+        // The top 53 bits fill an f64's mantissa exactly.
+        (rng.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+/// A sample from the standard normal distribution (mean 0, variance 1).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct StandardNormal(pub f64);
+
+impl Rand for StandardNormal {
+    fn rand<R: Rng>(rng: &mut R) -> StandardNormal {
+        // Box-Muller. `1 - u` keeps the logarithm's argument away from zero.
+        let u = 1.0 - f64::rand(rng);
+        let v = f64::rand(rng);
+        StandardNormal((-2.0 * u.ln()).sqrt() * (2.0 * std::f64::consts::PI * v).cos())
+    }
+}
+
+/// A fast pseudorandom number generator (Marsaglia's xorshift128). Not for
+/// cryptography, but the same seed always gives the same sequence.
+#[derive(Clone, Debug)]
+pub struct XorShiftRng {
+    state: [u32; 4],
+}
+
+impl XorShiftRng {
+    pub fn seed_from_u64(seed: u64) -> XorShiftRng {
+        // Spread the seed with splitmix64 so that small seeds still give
+        // well-mixed, never all-zero, state.
+        let mut z = seed;
+        let mut next = || {
+            z = z.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut x = z;
+            x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            x ^ (x >> 31)
+        };
+        let (a, b) = (next(), next());
+        let mut state = [a as u32, (a >> 32) as u32, b as u32, (b >> 32) as u32];
+        if state == [0; 4] {
+            state[0] = 1;
+        }
+        XorShiftRng { state }
+    }
+}
+
+impl Rng for XorShiftRng {
+    fn next_u32(&mut self) -> u32 {
+        let [x, y, z, w] = self.state;
+        let t = x ^ (x << 11);
+        let next = w ^ (w >> 19) ^ t ^ (t >> 8);
+        self.state = [y, z, w, next];
+        next
+    }
+}
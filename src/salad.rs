@@ -0,0 +1,237 @@
+//! The salads from "Which to Use": `Salad<V>` holds one kind of vegetable,
+//! while the default `Salad`, of `Box<dyn Vegetable>`, mixes them. Boxed
+//! vegetables can be inspected and downcast back to their concrete types.
+
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::iter::FromIterator;
+
+mod builder;
+mod tagged;
+
+pub use self::builder::{MaxOf, MaxSize, MustContain, Rule, SaladBuilder, Violations};
+pub use self::tagged::{Record, SaladError, SaladResult, Tagged, VegetableRegistry};
+
+/// What a vegetable brings to a salad, for rules about its make-up.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Category {
+    LeafyGreen,
+    Fruit,
+    Crunchy,
+    Protein,
+}
+
+impl fmt::Display for Category {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Category::LeafyGreen => "leafy green",
+            Category::Fruit => "fruit",
+            Category::Crunchy => "crunchy vegetable",
+            Category::Protein => "protein",
+        })
+    }
+}
+
+/// Something that can go in a salad.
+///
+/// Every `Vegetable` is also `Any`, so a `&dyn Vegetable` can be asked what
+/// it really is; see `is` and `downcast_ref` on `dyn Vegetable`.
+pub trait Vegetable: Any + fmt::Debug {
+    /// A short lowercase name, the same for every value of a type.
+    fn name(&self) -> &str;
+
+    fn category(&self) -> Category;
+}
+
+impl dyn Vegetable {
+    /// Is this a `T`?
+    pub fn is<T: Vegetable>(&self) -> bool {
+        (self as &dyn Any).is::<T>()
+    }
+
+    /// This vegetable as a `T`, if that's what it is.
+    pub fn downcast_ref<T: Vegetable>(&self) -> Option<&T> {
+        (self as &dyn Any).downcast_ref()
+    }
+
+    pub fn downcast_mut<T: Vegetable>(&mut self) -> Option<&mut T> {
+        (self as &mut dyn Any).downcast_mut()
+    }
+}
+
+/// A salad of `V`s: by default, any mix of vegetables.
+#[derive(Debug)]
+pub struct Salad<V = Box<dyn Vegetable>> {
+    pub veggies: Vec<V>,
+}
+
+impl<V> Salad<V> {
+    pub fn new() -> Salad<V> {
+        Salad { veggies: vec![] }
+    }
+
+    pub fn len(&self) -> usize {
+        self.veggies.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.veggies.is_empty()
+    }
+
+    pub fn push(&mut self, veg: V) {
+        self.veggies.push(veg);
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, V> {
+        self.veggies.iter()
+    }
+}
+
+impl<V> Default for Salad<V> {
+    fn default() -> Salad<V> {
+        Salad::new()
+    }
+}
+
+impl<V> FromIterator<V> for Salad<V> {
+    fn from_iter<I: IntoIterator<Item=V>>(iter: I) -> Salad<V> {
+        Salad { veggies: iter.into_iter().collect() }
+    }
+}
+
+impl Salad {
+    /// Add a vegetable of any type.
+    pub fn add<T: Vegetable>(&mut self, veg: T) {
+        self.veggies.push(Box::new(veg));
+    }
+
+    /// Every `T` in the salad, in order.
+    pub fn iter_of<T: Vegetable>(&self) -> impl Iterator<Item=&T> {
+        self.veggies.iter().filter_map(|v| v.downcast_ref::<T>())
+    }
+
+    pub fn iter_of_mut<T: Vegetable>(&mut self) -> impl Iterator<Item=&mut T> {
+        self.veggies.iter_mut().filter_map(|v| v.downcast_mut::<T>())
+    }
+
+    /// How many `T`s the salad holds.
+    pub fn count_of<T: Vegetable>(&self) -> usize {
+        self.veggies.iter().filter(|v| v.is::<T>()).count()
+    }
+
+    /// How many of each kind of vegetable the salad holds, by name.
+    pub fn counts(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for veg in &self.veggies {
+            *counts.entry(veg.name()).or_insert(0) += 1;
+        }
+        counts
+    }
+}
+
+/// Mixing up a salad of one vegetable.
+impl<V: Vegetable> From<Salad<V>> for Salad {
+    fn from(salad: Salad<V>) -> Salad {
+        Salad {
+            veggies: salad.veggies.into_iter()
+                .map(|v| Box::new(v) as Box<dyn Vegetable>)
+                .collect(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Lettuce {
+    pub grams: u32,
+}
+
+impl Vegetable for Lettuce {
+    fn name(&self) -> &str {
+        "lettuce"
+    }
+
+    fn category(&self) -> Category {
+        Category::LeafyGreen
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Tomato {
+    pub grams: u32,
+    pub ripe: bool,
+}
+
+impl Vegetable for Tomato {
+    fn name(&self) -> &str {
+        "tomato"
+    }
+
+    fn category(&self) -> Category {
+        Category::Fruit
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cucumber {
+    pub grams: u32,
+}
+
+impl Vegetable for Cucumber {
+    fn name(&self) -> &str {
+        "cucumber"
+    }
+
+    fn category(&self) -> Category {
+        Category::Crunchy
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chickpeas {
+    pub grams: u32,
+}
+
+impl Vegetable for Chickpeas {
+    fn name(&self) -> &str {
+        "chickpeas"
+    }
+
+    fn category(&self) -> Category {
+        Category::Protein
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mixed_salad() {
+        let tomatoes: Salad<Tomato> = vec![
+            Tomato { grams: 80, ripe: true },
+            Tomato { grams: 60, ripe: false },
+        ].into_iter().collect();
+
+        let mut salad = Salad::from(tomatoes);
+        salad.add(Lettuce { grams: 50 });
+        salad.add(Cucumber { grams: 40 });
+        salad.push(Box::new(Lettuce { grams: 30 }));
+
+        assert_eq!(salad.len(), 5);
+        assert!(salad.veggies[2].is::<Lettuce>());
+        assert_eq!(salad.veggies[3].downcast_ref::<Cucumber>(), Some(&Cucumber { grams: 40 }));
+        assert_eq!(salad.veggies[3].downcast_ref::<Tomato>(), None);
+
+        let lettuce: Vec<u32> = salad.iter_of::<Lettuce>().map(|l| l.grams).collect();
+        assert_eq!(lettuce, [50, 30]);
+        for tomato in salad.iter_of_mut::<Tomato>() {
+            tomato.ripe = true;
+        }
+        assert!(salad.iter_of::<Tomato>().all(|t| t.ripe));
+
+        assert_eq!(salad.count_of::<Chickpeas>(), 0);
+        let counts: Vec<_> = salad.counts().into_iter().collect();
+        assert_eq!(counts, [("cucumber", 1), ("lettuce", 2), ("tomato", 2)]);
+    }
+}
@@ -0,0 +1,185 @@
+//! Building salads that have to follow rules.
+
+use std::error::Error;
+use std::fmt;
+
+use super::{Category, Salad, Vegetable};
+
+/// A constraint on what a finished salad may contain.
+///
+/// Closures of type `Fn(&Salad) -> Result<(), String>` are rules too.
+pub trait Rule {
+    /// `Err` with a description of the problem if `salad` breaks this rule.
+    fn check(&self, salad: &Salad) -> Result<(), String>;
+}
+
+impl<F> Rule for F
+    where F: Fn(&Salad) -> Result<(), String>
+{
+    fn check(&self, salad: &Salad) -> Result<(), String> {
+        self(salad)
+    }
+}
+
+/// At most `max` vegetables of `category`.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxOf {
+    pub category: Category,
+    pub max: usize,
+}
+
+impl Rule for MaxOf {
+    fn check(&self, salad: &Salad) -> Result<(), String> {
+        let n = salad.iter().filter(|v| v.category() == self.category).count();
+        if n > self.max {
+            return Err(format!("no more than {} {}s allowed, found {}", self.max, self.category, n));
+        }
+        Ok(())
+    }
+}
+
+/// At least one vegetable of the given category.
+#[derive(Clone, Copy, Debug)]
+pub struct MustContain(pub Category);
+
+impl Rule for MustContain {
+    fn check(&self, salad: &Salad) -> Result<(), String> {
+        if !salad.iter().any(|v| v.category() == self.0) {
+            return Err(format!("must contain a {}", self.0));
+        }
+        Ok(())
+    }
+}
+
+/// At most this many vegetables in all.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxSize(pub usize);
+
+impl Rule for MaxSize {
+    fn check(&self, salad: &Salad) -> Result<(), String> {
+        if salad.len() > self.0 {
+            return Err(format!("no more than {} vegetables allowed, found {}", self.0, salad.len()));
+        }
+        Ok(())
+    }
+}
+
+/// Every rule a salad broke, in the order the rules were added.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Violations(pub Vec<String>);
+
+impl fmt::Display for Violations {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join("; "))
+    }
+}
+
+impl Error for Violations {}
+
+/// Collects vegetables and rules, and checks every rule when the salad is
+/// built.
+///
+/// ```
+/// # use traits_generics::salad::*;
+/// let result = SaladBuilder::new()
+///     .rule(MustContain(Category::Protein))
+///     .rule(MaxOf { category: Category::LeafyGreen, max: 1 })
+///     .with(Lettuce { grams: 40 })
+///     .with(Lettuce { grams: 40 })
+///     .build();
+/// assert_eq!(result.unwrap_err().0, ["must contain a protein",
+///                                    "no more than 1 leafy greens allowed, found 2"]);
+/// ```
+#[derive(Default)]
+pub struct SaladBuilder {
+    salad: Salad,
+    rules: Vec<Box<dyn Rule>>,
+}
+
+impl SaladBuilder {
+    pub fn new() -> SaladBuilder {
+        SaladBuilder::default()
+    }
+
+    pub fn with<V: Vegetable>(mut self, veg: V) -> SaladBuilder {
+        self.salad.add(veg);
+        self
+    }
+
+    pub fn with_boxed(mut self, veg: Box<dyn Vegetable>) -> SaladBuilder {
+        self.salad.push(veg);
+        self
+    }
+
+    /// Add every vegetable in `veggies`, all of one type.
+    pub fn with_all<V, I>(mut self, veggies: I) -> SaladBuilder
+        where V: Vegetable, I: IntoIterator<Item=V>
+    {
+        for veg in veggies {
+            self.salad.add(veg);
+        }
+        self
+    }
+
+    pub fn rule<R: Rule + 'static>(mut self, rule: R) -> SaladBuilder {
+        self.rules.push(Box::new(rule));
+        self
+    }
+
+    pub fn rule_boxed(mut self, rule: Box<dyn Rule>) -> SaladBuilder {
+        self.rules.push(rule);
+        self
+    }
+
+    /// The salad, if it passes every rule; otherwise every rule it broke.
+    pub fn build(self) -> Result<Salad, Violations> {
+        let violations: Vec<String> = self.rules.iter()
+            .filter_map(|rule| rule.check(&self.salad).err())
+            .collect();
+        if violations.is_empty() {
+            Ok(self.salad)
+        } else {
+            Err(Violations(violations))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::salad::{Chickpeas, Cucumber, Lettuce, Tomato};
+
+    fn rules() -> SaladBuilder {
+        SaladBuilder::new()
+            .rule(MaxOf { category: Category::LeafyGreen, max: 3 })
+            .rule(MustContain(Category::Protein))
+            .rule(MaxSize(6))
+            .rule(|salad: &Salad| {
+                if salad.iter_of::<Tomato>().any(|t| !t.ripe) {
+                    return Err("tomatoes must be ripe".to_string());
+                }
+                Ok(())
+            })
+    }
+
+    #[test]
+    fn test_builder() {
+        let salad = rules()
+            .with(Lettuce { grams: 40 })
+            .with_boxed(Box::new(Chickpeas { grams: 100 }))
+            .with_all(vec![Tomato { grams: 80, ripe: true }])
+            .build()
+            .unwrap();
+        assert_eq!(salad.len(), 3);
+
+        let err = rules()
+            .with_all((0..4).map(|_| Lettuce { grams: 40 }))
+            .with_boxed(Box::new(Cucumber { grams: 30 }))
+            .with(Tomato { grams: 70, ripe: false })
+            .build()
+            .unwrap_err();
+        assert_eq!(err.to_string(), "no more than 3 leafy greens allowed, found 4; \
+                                     must contain a protein; \
+                                     tomatoes must be ripe");
+    }
+}
@@ -0,0 +1,724 @@
+//! Saving and loading mixed salads.
+//!
+//! A `Box<dyn Vegetable>` doesn't say how to rebuild itself, so each
+//! vegetable type that wants to be saved implements `Tagged`, giving a tag
+//! name and a conversion to and from a `Record` of named values. A
+//! `VegetableRegistry` maps tags back to types, and writes salads as JSON or
+//! in a compact binary format.
+
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+
+use super::{Chickpeas, Cucumber, Lettuce, Salad, Tomato, Vegetable};
+use crate::mapreduce::Value;
+
+#[derive(Debug)]
+pub enum SaladError {
+    /// The input names a vegetable type that isn't registered.
+    UnknownTag(String),
+    /// The salad holds a vegetable whose type isn't registered, so it can't
+    /// be saved. Holds the vegetable's name.
+    Unregistered(String),
+    /// The input isn't in the expected format.
+    Malformed(String),
+    Io(io::Error),
+}
+
+impl fmt::Display for SaladError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SaladError::UnknownTag(tag) => write!(f, "unknown vegetable type `{}`", tag),
+            SaladError::Unregistered(name) => {
+                write!(f, "{} has no registered type tag, so it can't be saved", name)
+            }
+            SaladError::Malformed(msg) => write!(f, "malformed salad: {}", msg),
+            SaladError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SaladError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SaladError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SaladError {
+    fn from(e: io::Error) -> SaladError {
+        SaladError::Io(e)
+    }
+}
+
+pub type SaladResult<T> = Result<T, SaladError>;
+
+fn malformed<T>(msg: String) -> SaladResult<T> {
+    Err(SaladError::Malformed(msg))
+}
+
+/// A vegetable's saved fields, in order.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Record {
+    pub fields: Vec<(String, Value)>,
+}
+
+impl Record {
+    pub fn new() -> Record {
+        Record::default()
+    }
+
+    /// Add a field, builder style.
+    pub fn with<V: Into<Value>>(mut self, name: &str, value: V) -> Record {
+        self.fields.push((name.to_string(), value.into()));
+        self
+    }
+
+    pub fn get(&self, name: &str) -> SaladResult<&Value> {
+        match self.fields.iter().find(|(n, _)| n == name) {
+            Some((_, value)) => Ok(value),
+            None => malformed(format!("missing field `{}`", name)),
+        }
+    }
+
+    pub fn int(&self, name: &str) -> SaladResult<i64> {
+        match self.get(name)? {
+            Value::Int(i) => Ok(*i),
+            other => malformed(format!("field `{}` should be an integer, not {}", name, other)),
+        }
+    }
+
+    /// An integer field that must fit in a `u32`.
+    pub fn u32(&self, name: &str) -> SaladResult<u32> {
+        let i = self.int(name)?;
+        u32::try_from(i).or_else(|_| malformed(format!("field `{}` is out of range: {}", name, i)))
+    }
+
+    pub fn bool(&self, name: &str) -> SaladResult<bool> {
+        match self.get(name)? {
+            Value::Bool(b) => Ok(*b),
+            other => malformed(format!("field `{}` should be true or false, not {}", name, other)),
+        }
+    }
+
+    pub fn str(&self, name: &str) -> SaladResult<&str> {
+        match self.get(name)? {
+            Value::Str(s) => Ok(s),
+            other => malformed(format!("field `{}` should be a string, not {}", name, other)),
+        }
+    }
+}
+
+/// A vegetable type that can be saved and loaded.
+pub trait Tagged: Vegetable + Sized {
+    /// The name stored alongside each saved value. Must be unique within a
+    /// registry.
+    const TAG: &'static str;
+
+    fn to_record(&self) -> Record;
+
+    fn from_record(record: &Record) -> SaladResult<Self>;
+}
+
+impl Tagged for Lettuce {
+    const TAG: &'static str = "lettuce";
+
+    fn to_record(&self) -> Record {
+        Record::new().with("grams", i64::from(self.grams))
+    }
+
+    fn from_record(record: &Record) -> SaladResult<Lettuce> {
+        Ok(Lettuce { grams: record.u32("grams")? })
+    }
+}
+
+impl Tagged for Tomato {
+    const TAG: &'static str = "tomato";
+
+    fn to_record(&self) -> Record {
+        Record::new().with("grams", i64::from(self.grams)).with("ripe", self.ripe)
+    }
+
+    fn from_record(record: &Record) -> SaladResult<Tomato> {
+        Ok(Tomato { grams: record.u32("grams")?, ripe: record.bool("ripe")? })
+    }
+}
+
+impl Tagged for Cucumber {
+    const TAG: &'static str = "cucumber";
+
+    fn to_record(&self) -> Record {
+        Record::new().with("grams", i64::from(self.grams))
+    }
+
+    fn from_record(record: &Record) -> SaladResult<Cucumber> {
+        Ok(Cucumber { grams: record.u32("grams")? })
+    }
+}
+
+impl Tagged for Chickpeas {
+    const TAG: &'static str = "chickpeas";
+
+    fn to_record(&self) -> Record {
+        Record::new().with("grams", i64::from(self.grams))
+    }
+
+    fn from_record(record: &Record) -> SaladResult<Chickpeas> {
+        Ok(Chickpeas { grams: record.u32("grams")? })
+    }
+}
+
+fn encode<T: Tagged>(veg: &dyn Vegetable) -> Record {
+    veg.downcast_ref::<T>().expect("registry entry used for the wrong type").to_record()
+}
+
+fn decode<T: Tagged>(record: &Record) -> SaladResult<Box<dyn Vegetable>> {
+    Ok(Box::new(T::from_record(record)?))
+}
+
+struct Entry {
+    tag: &'static str,
+    encode: fn(&dyn Vegetable) -> Record,
+    decode: fn(&Record) -> SaladResult<Box<dyn Vegetable>>,
+}
+
+/// The vegetable types a salad can be saved with, by tag.
+#[derive(Default)]
+pub struct VegetableRegistry {
+    entries: Vec<Entry>,
+    by_tag: HashMap<&'static str, usize>,
+    by_type: HashMap<TypeId, usize>,
+}
+
+const MAGIC: &[u8; 4] = b"SLD1";
+
+impl VegetableRegistry {
+    pub fn new() -> VegetableRegistry {
+        VegetableRegistry::default()
+    }
+
+    /// A registry of every vegetable defined in this crate.
+    pub fn standard() -> VegetableRegistry {
+        let mut registry = VegetableRegistry::new();
+        registry.register::<Lettuce>();
+        registry.register::<Tomato>();
+        registry.register::<Cucumber>();
+        registry.register::<Chickpeas>();
+        registry
+    }
+
+    /// Make `T` saveable and loadable under `T::TAG`. Panics if another type
+    /// already has that tag.
+    pub fn register<T: Tagged>(&mut self) {
+        if let Some(&i) = self.by_type.get(&TypeId::of::<T>()) {
+            assert_eq!(self.entries[i].tag, T::TAG);
+            return;
+        }
+        assert!(!self.by_tag.contains_key(T::TAG), "two vegetable types tagged `{}`", T::TAG);
+        self.by_tag.insert(T::TAG, self.entries.len());
+        self.by_type.insert(TypeId::of::<T>(), self.entries.len());
+        self.entries.push(Entry { tag: T::TAG, encode: encode::<T>, decode: decode::<T> });
+    }
+
+    fn entry_for(&self, veg: &dyn Vegetable) -> SaladResult<&Entry> {
+        let id = (veg as &dyn Any).type_id();
+        match self.by_type.get(&id) {
+            Some(&i) => Ok(&self.entries[i]),
+            None => Err(SaladError::Unregistered(veg.name().to_string())),
+        }
+    }
+
+    fn rebuild(&self, tag: &str, record: &Record) -> SaladResult<Box<dyn Vegetable>> {
+        match self.by_tag.get(tag) {
+            Some(&i) => (self.entries[i].decode)(record),
+            None => Err(SaladError::UnknownTag(tag.to_string())),
+        }
+    }
+
+    /// The salad as a JSON object: `{"veggies": [...]}`, each vegetable an
+    /// object whose `"type"` member is its tag.
+    pub fn to_json(&self, salad: &Salad) -> SaladResult<String> {
+        let mut out = String::from("{\"veggies\":[");
+        for (i, veg) in salad.veggies.iter().enumerate() {
+            let entry = self.entry_for(&**veg)?;
+            if i > 0 {
+                out.push(',');
+            }
+            out.push_str("{\"type\":");
+            json::write_str(&mut out, entry.tag);
+            for (name, value) in (entry.encode)(&**veg).fields {
+                out.push(',');
+                json::write_str(&mut out, &name);
+                out.push(':');
+                json::write_value(&mut out, &value)?;
+            }
+            out.push('}');
+        }
+        out.push_str("]}");
+        Ok(out)
+    }
+
+    /// Read a salad written by `to_json`.
+    pub fn from_json(&self, text: &str) -> SaladResult<Salad> {
+        let root = json::parse(text)?;
+        let veggies = match root {
+            json::Json::Object(mut members) => {
+                match members.iter().position(|(k, _)| k == "veggies") {
+                    Some(i) => members.swap_remove(i).1,
+                    None => return malformed("no `veggies` member".to_string()),
+                }
+            }
+            _ => return malformed("expected an object".to_string()),
+        };
+        let items = match veggies {
+            json::Json::Array(items) => items,
+            _ => return malformed("`veggies` should be an array".to_string()),
+        };
+
+        let mut salad = Salad::new();
+        for item in items {
+            let mut record = Record::new();
+            let mut tag = None;
+            let members = match item {
+                json::Json::Object(members) => members,
+                _ => return malformed("each vegetable should be an object".to_string()),
+            };
+            for (name, value) in members {
+                match (name.as_str(), value) {
+                    ("type", json::Json::Value(Value::Str(t))) => tag = Some(t),
+                    ("type", _) => return malformed("`type` should be a string".to_string()),
+                    (_, json::Json::Value(v)) => record.fields.push((name, v)),
+                    _ => return malformed(format!("field `{}` should be a plain value", name)),
+                }
+            }
+            let tag = match tag {
+                Some(tag) => tag,
+                None => return malformed("vegetable without a `type`".to_string()),
+            };
+            salad.push(self.rebuild(&tag, &record)?);
+        }
+        Ok(salad)
+    }
+
+    /// Write the salad in a compact little-endian binary format.
+    pub fn save<W: Write>(&self, salad: &Salad, out: &mut W) -> SaladResult<()> {
+        out.write_all(MAGIC)?;
+        write_len(out, salad.len())?;
+        for veg in &salad.veggies {
+            let entry = self.entry_for(&**veg)?;
+            write_bytes(out, entry.tag.as_bytes())?;
+            let record = (entry.encode)(&**veg);
+            write_len(out, record.fields.len())?;
+            for (name, value) in &record.fields {
+                write_bytes(out, name.as_bytes())?;
+                match value {
+                    Value::Null => out.write_all(&[0])?,
+                    Value::Bool(b) => out.write_all(&[1, *b as u8])?,
+                    Value::Int(i) => {
+                        out.write_all(&[2])?;
+                        out.write_all(&i.to_le_bytes())?;
+                    }
+                    Value::Float(x) => {
+                        out.write_all(&[3])?;
+                        out.write_all(&x.to_le_bytes())?;
+                    }
+                    Value::Str(s) => {
+                        out.write_all(&[4])?;
+                        write_bytes(out, s.as_bytes())?;
+                    }
+                }
+            }
+        }
+        out.flush()?;
+        Ok(())
+    }
+
+    /// Read a salad written by `save`.
+    pub fn load<R: Read>(&self, input: &mut R) -> SaladResult<Salad> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return malformed("not a salad file".to_string());
+        }
+        let count = read_len(input)?;
+        let mut salad = Salad::new();
+        for _ in 0..count {
+            let tag = read_string(input)?;
+            let mut record = Record::new();
+            for _ in 0..read_len(input)? {
+                let name = read_string(input)?;
+                let value = match read_array::<_, 1>(input)?[0] {
+                    0 => Value::Null,
+                    1 => Value::Bool(read_array::<_, 1>(input)?[0] != 0),
+                    2 => Value::Int(i64::from_le_bytes(read_array(input)?)),
+                    3 => Value::Float(f64::from_le_bytes(read_array(input)?)),
+                    4 => Value::Str(read_string(input)?),
+                    other => return malformed(format!("unknown value type {}", other)),
+                };
+                record.fields.push((name, value));
+            }
+            salad.push(self.rebuild(&tag, &record)?);
+        }
+        Ok(salad)
+    }
+}
+
+fn write_len<W: Write>(out: &mut W, n: usize) -> SaladResult<()> {
+    match u32::try_from(n) {
+        Ok(n) => Ok(out.write_all(&n.to_le_bytes())?),
+        Err(_) => malformed(format!("too many items to save: {}", n)),
+    }
+}
+
+fn write_bytes<W: Write>(out: &mut W, bytes: &[u8]) -> SaladResult<()> {
+    write_len(out, bytes.len())?;
+    Ok(out.write_all(bytes)?)
+}
+
+fn read_array<R: Read, const N: usize>(input: &mut R) -> SaladResult<[u8; N]> {
+    let mut bytes = [0; N];
+    input.read_exact(&mut bytes)?;
+    Ok(bytes)
+}
+
+fn read_len<R: Read>(input: &mut R) -> SaladResult<usize> {
+    Ok(u32::from_le_bytes(read_array(input)?) as usize)
+}
+
+fn read_string<R: Read>(input: &mut R) -> SaladResult<String> {
+    let len = read_len(input)?;
+    let mut bytes = vec![];
+    input.take(len as u64).read_to_end(&mut bytes)?;
+    if bytes.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    String::from_utf8(bytes).or_else(|_| malformed("string isn't UTF-8".to_string()))
+}
+
+/// Just enough JSON for salads.
+mod json {
+    use super::{malformed, SaladResult};
+    use crate::mapreduce::Value;
+    use std::fmt::Write;
+
+    pub enum Json {
+        Value(Value),
+        Array(Vec<Json>),
+        Object(Vec<(String, Json)>),
+    }
+
+    pub fn write_str(out: &mut String, s: &str) {
+        out.push('"');
+        for c in s.chars() {
+            match c {
+                '"' => out.push_str("\\\""),
+                '\\' => out.push_str("\\\\"),
+                '\n' => out.push_str("\\n"),
+                '\r' => out.push_str("\\r"),
+                '\t' => out.push_str("\\t"),
+                c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+                c => out.push(c),
+            }
+        }
+        out.push('"');
+    }
+
+    pub fn write_value(out: &mut String, value: &Value) -> SaladResult<()> {
+        match value {
+            Value::Null => out.push_str("null"),
+            Value::Bool(b) => write!(out, "{}", b).unwrap(),
+            Value::Int(i) => write!(out, "{}", i).unwrap(),
+            // `{:?}` always includes a decimal point or exponent, so the
+            // number reads back as a float.
+            Value::Float(x) if x.is_finite() => write!(out, "{:?}", x).unwrap(),
+            Value::Float(x) => return malformed(format!("JSON can't represent {}", x)),
+            Value::Str(s) => write_str(out, s),
+        }
+        Ok(())
+    }
+
+    pub fn parse(text: &str) -> SaladResult<Json> {
+        let mut parser = Parser { text, pos: 0, depth: 0 };
+        let value = parser.value()?;
+        parser.skip_space();
+        if parser.pos < text.len() {
+            return parser.error("trailing characters");
+        }
+        Ok(value)
+    }
+
+    /// How deeply arrays and objects may nest, so hostile input can't
+    /// overflow the stack.
+    const MAX_DEPTH: usize = 64;
+
+    struct Parser<'a> {
+        text: &'a str,
+        pos: usize,
+        /// Arrays and objects currently open.
+        depth: usize,
+    }
+
+    impl<'a> Parser<'a> {
+        fn error<T>(&self, msg: &str) -> SaladResult<T> {
+            malformed(format!("{} at byte {}", msg, self.pos))
+        }
+
+        fn rest(&self) -> &'a str {
+            &self.text[self.pos..]
+        }
+
+        fn peek(&self) -> Option<char> {
+            self.rest().chars().next()
+        }
+
+        fn skip_space(&mut self) {
+            let rest = self.rest();
+            self.pos += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+        }
+
+        fn eat(&mut self, c: char) -> bool {
+            self.skip_space();
+            if self.peek() == Some(c) {
+                self.pos += 1;
+                true
+            } else {
+                false
+            }
+        }
+
+        fn expect(&mut self, c: char) -> SaladResult<()> {
+            if self.eat(c) {
+                Ok(())
+            } else {
+                self.error(&format!("expected `{}`", c))
+            }
+        }
+
+        /// Step into an array or object.
+        fn enter(&mut self) -> SaladResult<()> {
+            if self.depth == MAX_DEPTH {
+                return self.error("nested too deeply");
+            }
+            self.depth += 1;
+            self.pos += 1;
+            Ok(())
+        }
+
+        fn value(&mut self) -> SaladResult<Json> {
+            self.skip_space();
+            match self.peek() {
+                Some('{') => {
+                    self.enter()?;
+                    let mut members = vec![];
+                    if !self.eat('}') {
+                        loop {
+                            self.skip_space();
+                            let key = self.string()?;
+                            self.expect(':')?;
+                            members.push((key, self.value()?));
+                            if self.eat('}') {
+                                break;
+                            }
+                            self.expect(',')?;
+                        }
+                    }
+                    self.depth -= 1;
+                    Ok(Json::Object(members))
+                }
+                Some('[') => {
+                    self.enter()?;
+                    let mut items = vec![];
+                    if !self.eat(']') {
+                        loop {
+                            items.push(self.value()?);
+                            if self.eat(']') {
+                                break;
+                            }
+                            self.expect(',')?;
+                        }
+                    }
+                    self.depth -= 1;
+                    Ok(Json::Array(items))
+                }
+                Some('"') => Ok(Json::Value(Value::Str(self.string()?))),
+                Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+                _ => {
+                    for (word, value) in [("true", Value::Bool(true)),
+                                          ("false", Value::Bool(false)),
+                                          ("null", Value::Null)] {
+                        if self.rest().starts_with(word) {
+                            self.pos += word.len();
+                            return Ok(Json::Value(value));
+                        }
+                    }
+                    self.error("expected a value")
+                }
+            }
+        }
+
+        fn number(&mut self) -> SaladResult<Json> {
+            let rest = self.rest();
+            let len = rest.find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+                .unwrap_or(rest.len());
+            let text = &rest[..len];
+            let value = if text.contains(['.', 'e', 'E']) {
+                text.parse().ok().map(Value::Float)
+            } else {
+                text.parse().ok().map(Value::Int)
+            };
+            match value {
+                Some(value) => {
+                    self.pos += len;
+                    Ok(Json::Value(value))
+                }
+                None => self.error("bad number"),
+            }
+        }
+
+        /// The four hex digits of a `\u` escape.
+        fn hex4(&mut self) -> Option<u32> {
+            let code = self.rest().get(..4)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u32::from_str_radix(hex, 16).ok())?;
+            self.pos += 4;
+            Some(code)
+        }
+
+        /// The character of a `\u` escape, just after the `u`. Characters
+        /// outside the Basic Multilingual Plane take two escapes, a UTF-16
+        /// surrogate pair.
+        fn unicode_escape(&mut self) -> Option<char> {
+            let high = self.hex4()?;
+            if !(0xd800..0xdc00).contains(&high) {
+                return char::from_u32(high);
+            }
+            if !self.rest().starts_with("\\u") {
+                return None;
+            }
+            self.pos += 2;
+            let low = self.hex4().filter(|low| (0xdc00..0xe000).contains(low))?;
+            char::from_u32(0x10000 + ((high - 0xd800) << 10) + (low - 0xdc00))
+        }
+
+        fn string(&mut self) -> SaladResult<String> {
+            if self.peek() != Some('"') {
+                return self.error("expected a string");
+            }
+            self.pos += 1;
+            let mut s = String::new();
+            loop {
+                let c = match self.peek() {
+                    Some(c) => c,
+                    None => return self.error("unterminated string"),
+                };
+                self.pos += c.len_utf8();
+                match c {
+                    '"' => return Ok(s),
+                    '\\' => {
+                        let escape = self.peek();
+                        self.pos += 1;
+                        s.push(match escape {
+                            Some('"') => '"',
+                            Some('\\') => '\\',
+                            Some('/') => '/',
+                            Some('b') => '\u{8}',
+                            Some('f') => '\u{c}',
+                            Some('n') => '\n',
+                            Some('r') => '\r',
+                            Some('t') => '\t',
+                            Some('u') => match self.unicode_escape() {
+                                Some(c) => c,
+                                None => return self.error("unsupported \\u escape"),
+                            },
+                            _ => return self.error("bad escape"),
+                        });
+                    }
+                    c => s.push(c),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Salad {
+        let mut salad = Salad::new();
+        salad.add(Lettuce { grams: 50 });
+        salad.add(Tomato { grams: 80, ripe: true });
+        salad.add(Chickpeas { grams: 120 });
+        salad
+    }
+
+    fn describe(salad: &Salad) -> Vec<String> {
+        salad.iter().map(|v| format!("{:?}", v)).collect()
+    }
+
+    #[test]
+    fn test_round_trips() {
+        let registry = VegetableRegistry::standard();
+        let salad = sample();
+
+        let json = registry.to_json(&salad).unwrap();
+        assert_eq!(json, "{\"veggies\":[{\"type\":\"lettuce\",\"grams\":50},\
+                          {\"type\":\"tomato\",\"grams\":80,\"ripe\":true},\
+                          {\"type\":\"chickpeas\",\"grams\":120}]}");
+        let again = registry.from_json(&json).unwrap();
+        assert_eq!(describe(&again), describe(&salad));
+        assert_eq!(again.iter_of::<Tomato>().next(), Some(&Tomato { grams: 80, ripe: true }));
+
+        let mut bytes = vec![];
+        registry.save(&salad, &mut bytes).unwrap();
+        let again = registry.load(&mut &bytes[..]).unwrap();
+        assert_eq!(describe(&again), describe(&salad));
+
+        let err = registry.load(&mut &bytes[..bytes.len() - 1]).unwrap_err();
+        assert!(matches!(err, SaladError::Io(ref e) if e.kind() == io::ErrorKind::UnexpectedEof));
+    }
+
+    #[test]
+    fn test_unknown_types() {
+        let mut registry = VegetableRegistry::new();
+        registry.register::<Lettuce>();
+
+        let err = registry.to_json(&sample()).unwrap_err();
+        assert_eq!(err.to_string(), "tomato has no registered type tag, so it can't be saved");
+
+        let err = registry.from_json(r#"{"veggies": [{"type": "kale", "grams": 5}]}"#).unwrap_err();
+        assert!(matches!(err, SaladError::UnknownTag(ref tag) if tag == "kale"));
+
+        let mut bytes = vec![];
+        VegetableRegistry::standard().save(&sample(), &mut bytes).unwrap();
+        let err = registry.load(&mut &bytes[..]).unwrap_err();
+        assert!(matches!(err, SaladError::UnknownTag(ref tag) if tag == "tomato"));
+
+        let err = registry.from_json(r#"{"veggies": [{"type": "lettuce", "grams": -1}]}"#)
+            .unwrap_err();
+        assert_eq!(err.to_string(), "malformed salad: field `grams` is out of range: -1");
+    }
+
+    #[test]
+    fn test_json_edge_cases() {
+        let registry = VegetableRegistry::standard();
+        let salad = registry.from_json(r#"{"veggies": [{"type": "lett\u0075ce", "grams": 5}]}"#)
+            .unwrap();
+        assert_eq!(salad.count_of::<Lettuce>(), 1);
+
+        // A surrogate pair makes one character; half of one is no good.
+        let err = registry.from_json(r#"{"veggies": [{"type": "\uD83E\uDD57"}]}"#).unwrap_err();
+        assert!(matches!(err, SaladError::UnknownTag(ref tag) if tag == "\u{1F957}"));
+        let err = registry.from_json(r#"{"veggies": [{"type": "\uD83E"}]}"#).unwrap_err();
+        assert!(matches!(err, SaladError::Malformed(_)));
+
+        let deep = format!("{}{}", "[".repeat(100_000), "]".repeat(100_000));
+        let err = registry.from_json(&deep).unwrap_err();
+        assert!(err.to_string().starts_with("malformed salad: nested too deeply"), "{}", err);
+    }
+}
@@ -0,0 +1,254 @@
+//! A `Scene` holds every `Visible` thing in the game, draws them back to
+//! front, and works out which one a click lands on.
+
+use crate::canvas::Canvas;
+use crate::game::Visible;
+
+/// A handle to an object in a `Scene`. Handles stay valid until the object
+/// is removed, and are never reused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObjectId(usize);
+
+/// A handle to a group of objects in a `Scene`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct GroupId(usize);
+
+struct Entry {
+    visible: Box<dyn Visible>,
+    z: i32,
+    group: Option<GroupId>,
+}
+
+struct Group {
+    name: String,
+    shown: bool,
+}
+
+/// A collection of `Visible` objects in z-order: higher `z` is drawn later,
+/// on top, and objects with equal `z` stack in the order they were added.
+///
+/// Objects can be put in groups, which can be hidden and shown together.
+/// Hidden objects are neither drawn nor picked.
+#[derive(Default)]
+pub struct Scene {
+    /// Indexed by `ObjectId`; `None` once removed.
+    objects: Vec<Option<Entry>>,
+    groups: Vec<Group>,
+    selected: Option<ObjectId>,
+    hovered: Option<ObjectId>,
+}
+
+impl Scene {
+    pub fn new() -> Scene {
+        Scene::default()
+    }
+
+    pub fn add<V: Visible + 'static>(&mut self, visible: V, z: i32) -> ObjectId {
+        self.add_boxed(Box::new(visible), z)
+    }
+
+    pub fn add_boxed(&mut self, visible: Box<dyn Visible>, z: i32) -> ObjectId {
+        self.objects.push(Some(Entry { visible, z, group: None }));
+        ObjectId(self.objects.len() - 1)
+    }
+
+    /// Take an object out of the scene, dropping any selection or hover on
+    /// it.
+    pub fn remove(&mut self, id: ObjectId) -> Option<Box<dyn Visible>> {
+        let entry = self.objects.get_mut(id.0)?.take()?;
+        if self.selected == Some(id) {
+            self.selected = None;
+        }
+        if self.hovered == Some(id) {
+            self.hovered = None;
+        }
+        Some(entry.visible)
+    }
+
+    fn entry(&self, id: ObjectId) -> Option<&Entry> {
+        self.objects.get(id.0)?.as_ref()
+    }
+
+    fn entry_mut(&mut self, id: ObjectId) -> Option<&mut Entry> {
+        self.objects.get_mut(id.0)?.as_mut()
+    }
+
+    pub fn get(&self, id: ObjectId) -> Option<&dyn Visible> {
+        self.entry(id).map(|e| &*e.visible)
+    }
+
+    pub fn get_mut(&mut self, id: ObjectId) -> Option<&mut (dyn Visible + 'static)> {
+        self.entry_mut(id).map(|e| &mut *e.visible)
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects.iter().flatten().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn z(&self, id: ObjectId) -> Option<i32> {
+        self.entry(id).map(|e| e.z)
+    }
+
+    pub fn set_z(&mut self, id: ObjectId, z: i32) {
+        if let Some(e) = self.entry_mut(id) {
+            e.z = z;
+        }
+    }
+
+    /// Put an object above everything else.
+    pub fn bring_to_front(&mut self, id: ObjectId) {
+        let top = self.objects.iter().flatten().map(|e| e.z).max().unwrap_or(0);
+        if self.z(id).is_some_and(|z| z < top) {
+            self.set_z(id, top + 1);
+        }
+    }
+
+    pub fn add_group(&mut self, name: &str) -> GroupId {
+        self.groups.push(Group { name: name.to_string(), shown: true });
+        GroupId(self.groups.len() - 1)
+    }
+
+    pub fn group_name(&self, group: GroupId) -> &str {
+        &self.groups[group.0].name
+    }
+
+    /// Move an object into `group`, or out of any group with `None`.
+    pub fn set_group(&mut self, id: ObjectId, group: Option<GroupId>) {
+        if let Some(e) = self.entry_mut(id) {
+            e.group = group;
+        }
+    }
+
+    pub fn group_of(&self, id: ObjectId) -> Option<GroupId> {
+        self.entry(id)?.group
+    }
+
+    /// The objects in `group`, bottom first.
+    pub fn members(&self, group: GroupId) -> Vec<ObjectId> {
+        self.in_order().filter(|&id| self.group_of(id) == Some(group)).collect()
+    }
+
+    pub fn set_group_shown(&mut self, group: GroupId, shown: bool) {
+        self.groups[group.0].shown = shown;
+    }
+
+    pub fn is_shown(&self, id: ObjectId) -> bool {
+        match self.entry(id) {
+            Some(e) => e.group.is_none_or(|g| self.groups[g.0].shown),
+            None => false,
+        }
+    }
+
+    /// Every object, bottom first.
+    pub fn in_order(&self) -> impl Iterator<Item=ObjectId> {
+        let mut ids: Vec<ObjectId> = (0..self.objects.len())
+            .map(ObjectId)
+            .filter(|&id| self.entry(id).is_some())
+            .collect();
+        ids.sort_by_key(|&id| (self.objects[id.0].as_ref().unwrap().z, id));
+        ids.into_iter()
+    }
+
+    /// Draw every shown object, bottom first.
+    pub fn draw_all(&self, canvas: &mut Canvas) {
+        for id in self.in_order().filter(|&id| self.is_shown(id)) {
+            self.get(id).unwrap().draw(canvas);
+        }
+    }
+
+    /// The topmost shown object whose `hit_test` accepts (x, y).
+    pub fn pick(&self, x: i32, y: i32) -> Option<ObjectId> {
+        let ids: Vec<ObjectId> = self.in_order().collect();
+        ids.into_iter().rev()
+            .filter(|&id| self.is_shown(id))
+            .find(|&id| self.get(id).unwrap().hit_test(x, y))
+    }
+
+    /// Select whatever is at (x, y), or clear the selection if nothing is.
+    pub fn click(&mut self, x: i32, y: i32) -> Option<ObjectId> {
+        self.selected = self.pick(x, y);
+        self.selected
+    }
+
+    /// Note the pointer is at (x, y). Returns the object under it, if any.
+    pub fn hover(&mut self, x: i32, y: i32) -> Option<ObjectId> {
+        self.hovered = self.pick(x, y);
+        self.hovered
+    }
+
+    pub fn selected(&self) -> Option<ObjectId> {
+        self.selected
+    }
+
+    pub fn hovered(&self) -> Option<ObjectId> {
+        self.hovered
+    }
+
+    pub fn select(&mut self, id: Option<ObjectId>) {
+        self.selected = id.filter(|&id| self.entry(id).is_some());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Broom;
+    use crate::geometry::{Point, Rect};
+
+    /// A filled rectangle of one character.
+    struct Block(Rect, char);
+
+    impl Visible for Block {
+        fn draw(&self, canvas: &mut Canvas) {
+            for y in self.0.min.y() ..= self.0.max.y() {
+                for x in self.0.min.x() ..= self.0.max.x() {
+                    canvas.write_at(x, y, self.1);
+                }
+            }
+        }
+
+        fn bounds(&self) -> Rect {
+            self.0
+        }
+    }
+
+    #[test]
+    fn test_scene() {
+        let mut scene = Scene::new();
+        let wall = scene.add(Block(Rect::new(Point::xy(0, 0), Point::xy(4, 2)), '#'), 0);
+        let broom = scene.add(Broom::new(2, 2, 0), 1);
+        let crate_ = scene.add(Block(Rect::new(Point::xy(3, 1), Point::xy(4, 2)), 'x'), 0);
+
+        let mut canvas = Canvas::new(5, 3);
+        scene.draw_all(&mut canvas);
+        assert_eq!(canvas.to_string(), "#####\n##|xx\n##Mxx\n");
+
+        assert_eq!(scene.pick(2, 1), Some(broom));
+        assert_eq!(scene.pick(3, 2), Some(crate_));
+        assert_eq!(scene.pick(0, 0), Some(wall));
+        assert_eq!(scene.pick(5, 0), None);
+
+        scene.bring_to_front(wall);
+        assert_eq!(scene.click(2, 1), Some(wall));
+        assert_eq!(scene.hover(4, 1), Some(wall));
+
+        let props = scene.add_group("props");
+        scene.set_group(wall, Some(props));
+        scene.set_group(crate_, Some(props));
+        assert_eq!(scene.members(props), [crate_, wall]);
+        scene.set_group_shown(props, false);
+        assert_eq!(scene.pick(2, 1), Some(broom));
+        let mut canvas = Canvas::new(5, 3);
+        scene.draw_all(&mut canvas);
+        assert_eq!(canvas.to_string(), "     \n  |  \n  M  \n");
+
+        assert!(scene.remove(wall).is_some());
+        assert_eq!(scene.selected(), None);
+        assert_eq!(scene.hovered(), None);
+        assert_eq!(scene.len(), 2);
+    }
+}
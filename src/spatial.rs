@@ -0,0 +1,266 @@
+//! A uniform-grid spatial hash, so a click or a collision check only has to
+//! `hit_test` the objects near it rather than every object on the map.
+
+use std::collections::{BTreeSet, HashMap};
+use std::hash::Hash;
+
+use crate::game::Visible;
+use crate::geometry::{Point, Rect};
+
+/// Indexes keys by their bounding rectangles, on a grid of square buckets
+/// `cell_size` cells across. A key is listed in every bucket its rectangle
+/// touches, unless that's more than `MAX_BUCKETS_PER_KEY` buckets: such
+/// large keys are kept aside and checked by every query instead.
+///
+/// Queries return candidates whose bounds match; for pixel-precise results,
+/// confirm them with `Visible::hit_test`.
+#[derive(Clone, Debug)]
+pub struct SpatialHash<K> {
+    cell_size: i32,
+    buckets: HashMap<(i32, i32), Vec<K>>,
+    bounds: HashMap<K, Rect>,
+    large: BTreeSet<K>,
+}
+
+/// The most buckets a key is listed in; bigger keys go in the large set.
+pub const MAX_BUCKETS_PER_KEY: u64 = 64;
+
+/// The range of buckets, inclusive, that `rect` touches.
+type BucketRange = ((i32, i32), (i32, i32));
+
+impl<K: Copy + Eq + Hash + Ord> SpatialHash<K> {
+    /// Panics if `cell_size` isn't positive.
+    pub fn new(cell_size: i32) -> SpatialHash<K> {
+        assert!(cell_size > 0, "spatial hash cells must have a positive size");
+        SpatialHash {
+            cell_size,
+            buckets: HashMap::new(),
+            bounds: HashMap::new(),
+            large: BTreeSet::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.bounds.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.bounds.is_empty()
+    }
+
+    pub fn bounds(&self, key: K) -> Option<Rect> {
+        self.bounds.get(&key).copied()
+    }
+
+    fn bucket_range(&self, rect: &Rect) -> BucketRange {
+        let bucket = |p: Point<i32, 2>| (p.x().div_euclid(self.cell_size), p.y().div_euclid(self.cell_size));
+        (bucket(rect.min), bucket(rect.max))
+    }
+
+    fn bucket_count(range: BucketRange) -> u64 {
+        let ((x0, y0), (x1, y1)) = range;
+        let span = |a: i32, b: i32| (i64::from(b) - i64::from(a) + 1).max(0) as u64;
+        span(x0, x1) * span(y0, y1)
+    }
+
+    fn is_large(range: BucketRange) -> bool {
+        Self::bucket_count(range) > MAX_BUCKETS_PER_KEY
+    }
+
+    fn buckets_in(range: BucketRange) -> impl Iterator<Item=(i32, i32)> {
+        let ((x0, y0), (x1, y1)) = range;
+        (y0..=y1).flat_map(move |y| (x0..=x1).map(move |x| (x, y)))
+    }
+
+    fn contains_bucket(range: BucketRange, (x, y): (i32, i32)) -> bool {
+        let ((x0, y0), (x1, y1)) = range;
+        x0 <= x && x <= x1 && y0 <= y && y <= y1
+    }
+
+    fn unlink(&mut self, key: K, bucket: (i32, i32)) {
+        if let Some(keys) = self.buckets.get_mut(&bucket) {
+            keys.retain(|&k| k != key);
+            if keys.is_empty() {
+                self.buckets.remove(&bucket);
+            }
+        }
+    }
+
+    /// List `key` in every bucket of `range`, or in the large set.
+    fn link_all(&mut self, key: K, range: BucketRange) {
+        if Self::is_large(range) {
+            self.large.insert(key);
+        } else {
+            for bucket in Self::buckets_in(range) {
+                self.buckets.entry(bucket).or_default().push(key);
+            }
+        }
+    }
+
+    fn unlink_all(&mut self, key: K, range: BucketRange) {
+        if Self::is_large(range) {
+            self.large.remove(&key);
+        } else {
+            for bucket in Self::buckets_in(range) {
+                self.unlink(key, bucket);
+            }
+        }
+    }
+
+    /// Add `key` with bounds `rect`, or move it there if it's already
+    /// indexed. Only the buckets it enters or leaves are touched.
+    pub fn insert(&mut self, key: K, rect: Rect) {
+        let new = self.bucket_range(&rect);
+        match self.bounds.insert(key, rect) {
+            Some(old_rect) => {
+                let old = self.bucket_range(&old_rect);
+                if old == new {
+                    return;
+                }
+                if Self::is_large(old) || Self::is_large(new) {
+                    self.unlink_all(key, old);
+                    self.link_all(key, new);
+                    return;
+                }
+                for bucket in Self::buckets_in(old).filter(|&b| !Self::contains_bucket(new, b)) {
+                    self.unlink(key, bucket);
+                }
+                for bucket in Self::buckets_in(new).filter(|&b| !Self::contains_bucket(old, b)) {
+                    self.buckets.entry(bucket).or_default().push(key);
+                }
+            }
+            None => self.link_all(key, new),
+        }
+    }
+
+    /// Bring `key`'s entry up to date with `visible`'s current bounds: call
+    /// this whenever a creature's `position()` may have changed. Does
+    /// nothing if the bounds are unchanged.
+    pub fn update<V: Visible + ?Sized>(&mut self, key: K, visible: &V) {
+        let rect = visible.bounds();
+        if self.bounds(key) != Some(rect) {
+            self.insert(key, rect);
+        }
+    }
+
+    pub fn remove(&mut self, key: K) -> Option<Rect> {
+        let rect = self.bounds.remove(&key)?;
+        self.unlink_all(key, self.bucket_range(&rect));
+        Some(rect)
+    }
+
+    /// Every key whose bounds contain (x, y), in order.
+    pub fn query_point(&self, x: i32, y: i32) -> Vec<K> {
+        let p = Point::xy(x, y);
+        self.query_rect(&Rect::new(p, p))
+    }
+
+    /// Every key whose bounds overlap `rect`, in order.
+    pub fn query_rect(&self, rect: &Rect) -> Vec<K> {
+        let range = self.bucket_range(rect);
+        let mut candidates: Vec<K> = self.large.iter().copied().collect();
+        // A query bigger than the occupied buckets looks through those
+        // rather than every bucket it covers.
+        if Self::bucket_count(range) > self.buckets.len() as u64 {
+            for (&bucket, keys) in &self.buckets {
+                if Self::contains_bucket(range, bucket) {
+                    candidates.extend(keys);
+                }
+            }
+        } else {
+            for bucket in Self::buckets_in(range) {
+                candidates.extend(self.buckets.get(&bucket).into_iter().flatten());
+            }
+        }
+        let found: BTreeSet<K> = candidates.into_iter()
+            .filter(|key| self.bounds[key].intersects(rect))
+            .collect();
+        found.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Broom;
+    use crate::rand::{Rng, XorShiftRng};
+
+    fn random_rect(rng: &mut XorShiftRng) -> Rect {
+        let mut coord = || rng.below(200) as i32 - 100;
+        let (x, y) = (coord(), coord());
+        let (w, h) = (rng.below(30) as i32, rng.below(30) as i32);
+        Rect::new(Point::xy(x, y), Point::xy(x + w, y + h))
+    }
+
+    #[test]
+    fn test_matches_brute_force() {
+        let mut rng = XorShiftRng::seed_from_u64(44);
+        let mut index = SpatialHash::new(16);
+        let mut rects = HashMap::new();
+        for step in 0..2000 {
+            let key = rng.below(300) as usize;
+            if step % 7 == 0 {
+                assert_eq!(index.remove(key), rects.remove(&key));
+            } else {
+                let rect = random_rect(&mut rng);
+                index.insert(key, rect);
+                rects.insert(key, rect);
+            }
+        }
+        assert_eq!(index.len(), rects.len());
+
+        for _ in 0..200 {
+            let query = random_rect(&mut rng);
+            let mut expected: Vec<usize> = rects.iter()
+                .filter(|(_, r)| r.intersects(&query))
+                .map(|(&k, _)| k)
+                .collect();
+            expected.sort();
+            assert_eq!(index.query_rect(&query), expected);
+
+            let (x, y) = (query.min.x(), query.min.y());
+            let at_point: Vec<usize> = expected.iter().copied()
+                .filter(|k| rects[k].contains(&Point::xy(x, y)))
+                .collect();
+            assert_eq!(index.query_point(x, y), at_point);
+        }
+    }
+
+    #[test]
+    fn test_follows_creature() {
+        let mut index = SpatialHash::new(4);
+        let mut broom = Broom::new(1, 5, 2);
+        index.update(0, &broom);
+        assert_eq!(index.query_point(1, 2), [0]);
+
+        broom.x = 9;
+        index.update(0, &broom);
+        assert!(index.query_point(1, 2).is_empty());
+        assert_eq!(index.query_rect(&Rect::new(Point::xy(8, 0), Point::xy(12, 3))), [0]);
+        assert_eq!(index.buckets.values().map(Vec::len).sum::<usize>(), 2);
+    }
+
+    #[test]
+    fn test_huge_rects() {
+        let mut index = SpatialHash::new(1);
+        let far = 1_000_000_000;
+        let huge = Rect::new(Point::xy(-far, -far), Point::xy(far, far));
+        let small = Rect::new(Point::xy(5, 5), Point::xy(6, 7));
+        index.insert(0, huge);
+        index.insert(1, small);
+        assert_eq!(index.buckets.len(), 6);
+        assert_eq!(index.query_rect(&huge), [0, 1]);
+        assert_eq!(index.query_point(-50, 3), [0]);
+        assert_eq!(index.query_point(6, 6), [0, 1]);
+
+        // Shrinking the big key lists it in buckets like any other.
+        index.insert(0, Rect::new(Point::xy(0, 0), Point::xy(1, 0)));
+        assert!(index.large.is_empty());
+        assert_eq!(index.query_rect(&huge), [0, 1]);
+        assert_eq!(index.query_point(-50, 3), Vec::<usize>::new());
+        index.insert(1, huge);
+        assert_eq!(index.remove(1), Some(huge));
+        assert!(index.large.is_empty());
+        assert_eq!(index.buckets.len(), 2);
+    }
+}
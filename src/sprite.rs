@@ -0,0 +1,510 @@
+//! Sprites: ASCII art with colours and animation, loaded from text, and
+//! `SpriteVisible`, which puts one in the game as a `Visible` creature.
+//!
+//! ```text
+//! # A broom, swaying in the breeze.
+//! size: 3 x 3
+//! anchor: 1, 2
+//! transparent: .
+//! color y: yellow
+//! color b: blue on yellow
+//! frame: 0.5
+//! .|.
+//! .|.
+//! /M\
+//! colors:
+//! .y.
+//! .y.
+//! bbb
+//! frame: 0.25
+//! |..
+//! .|.
+//! /M\
+//! ```
+//!
+//! `size` comes first, at most `MAX_SIZE` cells each way, and each
+//! `frame:` line, giving how many seconds the frame shows for, is followed
+//! by exactly `size` rows of art; short rows are padded with transparent
+//! cells. The art may be followed by a `colors:` map of the same shape, no
+//! wider, whose characters are keys defined by `color` lines; spaces and
+//! the transparent character keep the default colours. The `anchor` is the
+//! cell of the art that sits at the sprite's position, (0, 0) if not given.
+//! `#` starts a comment, and blank lines are ignored, everywhere but inside
+//! art and colour maps.
+
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use std::str::FromStr;
+
+use crate::canvas::{Canvas, Cell, Color};
+use crate::game::{Creature, Direction, Visible};
+use crate::geometry::{Point, Rect};
+
+#[derive(Debug)]
+pub enum SpriteError {
+    /// A problem with the sprite text, on a 1-based line.
+    Syntax { line: usize, message: String },
+    Io(io::Error),
+}
+
+impl fmt::Display for SpriteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpriteError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+            SpriteError::Io(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for SpriteError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            SpriteError::Io(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for SpriteError {
+    fn from(e: io::Error) -> SpriteError {
+        SpriteError::Io(e)
+    }
+}
+
+pub type SpriteResult<T> = Result<T, SpriteError>;
+
+/// The most cells a sprite can be across or down.
+pub const MAX_SIZE: i32 = 1000;
+
+/// One frame of a sprite's animation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Frame {
+    /// How long the frame shows for.
+    pub seconds: f64,
+    /// Row by row; `None` where the frame is transparent.
+    cells: Vec<Option<Cell>>,
+    width: i32,
+}
+
+impl Frame {
+    /// The cell at (x, y) of the art, or `None` if it's transparent or
+    /// outside the frame.
+    pub fn cell(&self, x: i32, y: i32) -> Option<Cell> {
+        if 0 <= x && x < self.width && 0 <= y {
+            *self.cells.get((y * self.width + x) as usize)?
+        } else {
+            None
+        }
+    }
+}
+
+/// An animated piece of ASCII art. Every frame is the same size.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sprite {
+    width: i32,
+    height: i32,
+    anchor: (i32, i32),
+    frames: Vec<Frame>,
+}
+
+fn color_named(name: &str) -> Option<Color> {
+    Some(match name {
+        "default" => Color::Default,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::White,
+        _ => return None,
+    })
+}
+
+fn error_at<T>(line: usize, message: String) -> SpriteResult<T> {
+    Err(SpriteError::Syntax { line, message })
+}
+
+/// Reads sprite text line by line, remembering where it is for errors.
+struct Parser<'a> {
+    lines: Vec<&'a str>,
+    next: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error<T>(&self, message: String) -> SpriteResult<T> {
+        error_at(self.next, message)
+    }
+
+    /// The next line that isn't blank or a comment, trimmed.
+    fn statement(&mut self) -> Option<&'a str> {
+        while self.next < self.lines.len() {
+            let line = self.lines[self.next];
+            self.next += 1;
+            let line = line.split('#').next().unwrap().trim();
+            if !line.is_empty() {
+                return Some(line);
+            }
+        }
+        None
+    }
+
+    /// The next `height` lines, verbatim, each with its line number.
+    fn block(&mut self, height: i32, what: &str) -> SpriteResult<Vec<(usize, &'a str)>> {
+        let mut rows = vec![];
+        for _ in 0..height {
+            match self.lines.get(self.next) {
+                Some(row) => {
+                    self.next += 1;
+                    rows.push((self.next, *row));
+                }
+                None => return self.error(format!("{} ends after {} of {} rows", what, rows.len(), height)),
+            }
+        }
+        Ok(rows)
+    }
+
+    fn number<T: FromStr>(&self, text: &str) -> SpriteResult<T> {
+        match text.trim().parse() {
+            Ok(n) => Ok(n),
+            Err(_) => self.error(format!("expected a number, found `{}`", text.trim())),
+        }
+    }
+
+    fn pair(&self, text: &str, separator: char) -> SpriteResult<(i32, i32)> {
+        match text.split_once(separator) {
+            Some((a, b)) => Ok((self.number(a)?, self.number(b)?)),
+            None => self.error(format!("expected two numbers separated by `{}`", separator)),
+        }
+    }
+}
+
+impl Sprite {
+    /// Parse a sprite from the text format described in the module docs.
+    pub fn parse(text: &str) -> SpriteResult<Sprite> {
+        let mut parser = Parser { lines: text.lines().collect(), next: 0 };
+        let mut size = None;
+        let mut anchor = (0, 0);
+        let mut transparent = ' ';
+        let mut colors: HashMap<char, (Color, Color)> = HashMap::new();
+        let mut frames: Vec<Frame> = vec![];
+
+        while let Some(line) = parser.statement() {
+            let (key, value) = match line.split_once(':') {
+                Some((key, value)) => (key.trim(), value.trim()),
+                None => return parser.error(format!("expected `key: value`, found `{}`", line)),
+            };
+            let (width, height) = match (key, size) {
+                ("size", None) => {
+                    let (w, h) = parser.pair(value, 'x')?;
+                    if w < 1 || h < 1 {
+                        return parser.error("a sprite must be at least 1 x 1".to_string());
+                    }
+                    if w > MAX_SIZE || h > MAX_SIZE {
+                        return parser.error(format!("a sprite can't be more than {} x {}", MAX_SIZE, MAX_SIZE));
+                    }
+                    size = Some((w, h));
+                    continue;
+                }
+                ("size", Some(_)) => return parser.error("size is already given".to_string()),
+                (_, None) => return parser.error("the size must come first".to_string()),
+                (_, Some(size)) => size,
+            };
+            match key {
+                "anchor" => anchor = parser.pair(value, ',')?,
+                "transparent" => {
+                    let mut chars = value.chars();
+                    match (chars.next(), chars.next()) {
+                        (Some(ch), None) => transparent = ch,
+                        _ => return parser.error("expected a single transparent character".to_string()),
+                    }
+                }
+                _ if key.starts_with("color ") => {
+                    let mut chars = key["color ".len()..].trim().chars();
+                    let name = match (chars.next(), chars.next()) {
+                        (Some(ch), None) => ch,
+                        _ => return parser.error("a colour key must be a single character".to_string()),
+                    };
+                    let (fg, bg) = value.split_once(" on ").unwrap_or((value, "default"));
+                    match (color_named(fg.trim()), color_named(bg.trim())) {
+                        (Some(fg), Some(bg)) => colors.insert(name, (fg, bg)),
+                        _ => return parser.error(format!("unknown colours `{}`", value)),
+                    };
+                }
+                "frame" => {
+                    let seconds: f64 = parser.number(value)?;
+                    if !seconds.is_finite() || seconds <= 0.0 {
+                        return parser.error("a frame must show for some time".to_string());
+                    }
+                    let mut cells = vec![];
+                    for (line, row) in parser.block(height, "frame")? {
+                        let mut row: Vec<char> = row.chars().collect();
+                        if row.len() > width as usize {
+                            return error_at(line, format!("art is wider than {} cells", width));
+                        }
+                        row.resize(width as usize, transparent);
+                        cells.extend(row.into_iter().map(|ch| {
+                            if ch == transparent { None } else { Some(Cell::new(ch, Color::Default, Color::Default)) }
+                        }));
+                    }
+                    frames.push(Frame { seconds, cells, width });
+                }
+                "colors" => {
+                    let frame = match frames.last_mut() {
+                        Some(frame) => frame,
+                        None => return parser.error("colours must follow a frame".to_string()),
+                    };
+                    let rows = parser.block(height, "colour map")?;
+                    for (y, (line, row)) in rows.into_iter().enumerate() {
+                        if row.chars().count() > width as usize {
+                            return error_at(line, format!("colour map is wider than {} cells", width));
+                        }
+                        for (x, key) in row.chars().enumerate() {
+                            if key == ' ' || key == transparent {
+                                continue;
+                            }
+                            let (fg, bg) = match colors.get(&key) {
+                                Some(&pair) => pair,
+                                None => return error_at(line, format!("no colour is defined for `{}`", key)),
+                            };
+                            if let Some(cell) = &mut frame.cells[y * width as usize + x] {
+                                cell.fg = fg;
+                                cell.bg = bg;
+                            }
+                        }
+                    }
+                }
+                _ => return parser.error(format!("unknown key `{}`", key)),
+            }
+        }
+
+        let (width, height) = match size {
+            Some(size) => size,
+            None => return parser.error("no size given".to_string()),
+        };
+        if frames.is_empty() {
+            return parser.error("a sprite needs at least one frame".to_string());
+        }
+        Ok(Sprite { width, height, anchor, frames })
+    }
+
+    /// Read and parse a sprite file.
+    pub fn load<P: AsRef<Path>>(path: P) -> SpriteResult<Sprite> {
+        Sprite::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn anchor(&self) -> (i32, i32) {
+        self.anchor
+    }
+
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    /// How long the whole animation takes, once through.
+    pub fn duration(&self) -> f64 {
+        self.frames.iter().map(|f| f.seconds).sum()
+    }
+
+    /// The frame showing `time` seconds into the animation, which loops.
+    pub fn frame_at(&self, time: f64) -> &Frame {
+        let mut time = time.rem_euclid(self.duration());
+        for frame in &self.frames {
+            if time < frame.seconds {
+                return frame;
+            }
+            time -= frame.seconds;
+        }
+        // Only reachable through rounding at the very end of the loop.
+        self.frames.last().unwrap()
+    }
+}
+
+impl FromStr for Sprite {
+    type Err = SpriteError;
+
+    fn from_str(text: &str) -> SpriteResult<Sprite> {
+        Sprite::parse(text)
+    }
+}
+
+/// A sprite placed in the game: its anchor cell sits at (x, y), and it
+/// shows whichever frame its own clock has reached.
+///
+/// `S` is how the sprite is held; the default `Rc` lets many objects share
+/// one loaded sprite.
+#[derive(Clone, Debug)]
+pub struct SpriteVisible<S = Rc<Sprite>> {
+    pub sprite: S,
+    pub x: i32,
+    pub y: i32,
+    pub facing: Direction,
+    time: f64,
+}
+
+impl<S: Borrow<Sprite>> SpriteVisible<S> {
+    /// Place `sprite` at (x, y), facing east, at the start of its
+    /// animation.
+    pub fn new(sprite: S, x: i32, y: i32) -> SpriteVisible<S> {
+        SpriteVisible { sprite, x, y, facing: Direction::East, time: 0.0 }
+    }
+
+    /// How far into the animation this is, in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn set_time(&mut self, seconds: f64) {
+        self.time = seconds;
+    }
+
+    /// Let `seconds` of animation pass.
+    pub fn advance(&mut self, seconds: f64) {
+        self.time += seconds;
+    }
+
+    pub fn frame(&self) -> &Frame {
+        self.sprite.borrow().frame_at(self.time)
+    }
+
+    /// The canvas position of the art's top left cell.
+    fn origin(&self) -> (i32, i32) {
+        let (ax, ay) = self.sprite.borrow().anchor();
+        (self.x - ax, self.y - ay)
+    }
+}
+
+impl<S: Borrow<Sprite>> Visible for SpriteVisible<S> {
+    fn draw(&self, canvas: &mut Canvas) {
+        let sprite = self.sprite.borrow();
+        let (left, top) = self.origin();
+        let frame = self.frame();
+        for y in 0..sprite.height() {
+            for x in 0..sprite.width() {
+                if let Some(cell) = frame.cell(x, y) {
+                    canvas.put(left + x, top + y, cell);
+                }
+            }
+        }
+    }
+
+    fn bounds(&self) -> Rect {
+        let sprite = self.sprite.borrow();
+        let (left, top) = self.origin();
+        Rect::new(Point::xy(left, top), Point::xy(left + sprite.width() - 1, top + sprite.height() - 1))
+    }
+
+    /// Only the cells the current frame draws on count.
+    fn hit_test(&self, x: i32, y: i32) -> bool {
+        let (left, top) = self.origin();
+        self.frame().cell(x - left, y - top).is_some()
+    }
+}
+
+impl<S: Borrow<Sprite>> Creature for SpriteVisible<S> {
+    fn position(&self) -> (i32, i32) {
+        (self.x, self.y)
+    }
+
+    fn facing(&self) -> Direction {
+        self.facing
+    }
+
+    fn move_to(&mut self, x: i32, y: i32) {
+        self.x = x;
+        self.y = y;
+    }
+
+    fn turn_to(&mut self, facing: Direction) {
+        self.facing = facing;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BROOM: &str = "\
+# A broom, swaying in the breeze.
+size: 3 x 3
+anchor: 1, 2
+transparent: .
+color y: yellow
+color b: blue on yellow
+frame: 0.5
+.|.
+.|.
+/M\\
+colors:
+.y.
+.y.
+bbb
+frame: 0.25   # leaning
+|
+.|.
+/M\\
+";
+
+    #[test]
+    fn test_sprite_visible() {
+        let sprite = Rc::new(BROOM.parse::<Sprite>().unwrap());
+        assert_eq!((sprite.width(), sprite.height(), sprite.anchor()), (3, 3, (1, 2)));
+        assert_eq!(sprite.duration(), 0.75);
+
+        let mut broom = SpriteVisible::new(Rc::clone(&sprite), 2, 3);
+        let mut canvas = Canvas::new(5, 4);
+        canvas.write_str(0, 1, "~~~~~");
+        broom.draw(&mut canvas);
+        assert_eq!(canvas.to_string(), "     \n~~|~~\n  |  \n /M\\ \n");
+        assert_eq!(canvas.cell(2, 2), Some(Cell::new('|', Color::Yellow, Color::Default)));
+        assert_eq!(canvas.cell(1, 3), Some(Cell::new('/', Color::Blue, Color::Yellow)));
+
+        assert_eq!(broom.bounds(), Rect::new(Point::xy(1, 1), Point::xy(3, 3)));
+        assert!(broom.hit_test(2, 1));
+        assert!(!broom.hit_test(1, 1));
+
+        // Into the second frame, where the top of the stick leans left, and
+        // out the other side back to the first.
+        broom.advance(0.6);
+        assert!(broom.hit_test(1, 1));
+        assert!(!broom.hit_test(2, 1));
+        assert_eq!(broom.frame().cell(1, 1), Some(Cell::new('|', Color::Default, Color::Default)));
+        broom.advance(0.2);
+        assert!(broom.hit_test(2, 1));
+    }
+
+    #[test]
+    fn test_errors() {
+        let line = |text: &str| match Sprite::parse(text) {
+            Err(SpriteError::Syntax { line, .. }) => line,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(line("anchor: 0, 0\nsize: 1 x 1"), 1);
+        assert_eq!(line("size: 2 x 2\n\nframe: 1\nab\nabc\n"), 5);
+        assert_eq!(line("size: 1 x 2\nframe: 1\na\n"), 3);
+        assert_eq!(line("size: 1 x 1\nframe: 1\na\ncolors:\nq\n"), 5);
+        // Errors inside art and colour maps are on the row at fault, even
+        // when it isn't the last.
+        assert_eq!(line("size: 2 x 2\nframe: 1\nabc\nab\n"), 3);
+        assert_eq!(line("size: 1 x 2\nframe: 1\na\na\ncolors:\nq\n \n"), 6);
+        assert_eq!(line("size: 1 x 2\ncolor y: red\nframe: 1\na\na\ncolors:\nyy\ny\n"), 7);
+        assert_eq!(line("size: 1 x 1001\n"), 1);
+        assert_eq!(line("size: 1 x 1\n# nothing to see\n"), 2);
+        assert!(matches!(Sprite::load("/no/such/sprite"), Err(SpriteError::Io(_))));
+    }
+}
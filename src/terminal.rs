@@ -0,0 +1,200 @@
+//! Putting a `Canvas` on a real terminal without flicker: a `Terminal`
+//! remembers the frame it last drew and sends only the cells that changed,
+//! as ANSI cursor moves and colour changes, through any `Write`.
+
+use std::io::{self, Write};
+
+use crate::canvas::{Canvas, Cell, Color};
+
+/// How a `Terminal` writes its frames.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Mode {
+    /// ANSI escape sequences, redrawing only what changed.
+    Ansi,
+    /// Plain text, the whole frame every time, for output that isn't a
+    /// terminal: logs, pipes, dumb terminals.
+    Plain,
+}
+
+/// The foreground SGR parameter for `color`; add 10 for the background.
+fn sgr(color: Color) -> u8 {
+    match color {
+        Color::Default => 39,
+        Color::Black => 30,
+        Color::Red => 31,
+        Color::Green => 32,
+        Color::Yellow => 33,
+        Color::Blue => 34,
+        Color::Magenta => 35,
+        Color::Cyan => 36,
+        Color::White => 37,
+    }
+}
+
+/// What's shown for a character that wouldn't take up exactly one cell.
+pub const PLACEHOLDER: char = '?';
+
+/// Does `ch` take up other than one cell on a terminal? Control characters
+/// move the cursor or start escape sequences, wide characters (CJK, most
+/// emoji) take two cells and combining marks none. The ranges cover the
+/// common cases rather than every character Unicode makes wide.
+fn misfit(ch: char) -> bool {
+    ch.is_control() || matches!(ch as u32,
+        0x0300..=0x036F | 0x200B..=0x200F | 0xFE00..=0xFE0F
+        | 0x1100..=0x115F | 0x2E80..=0x303E | 0x3041..=0x33FF | 0x3400..=0x4DBF
+        | 0x4E00..=0x9FFF | 0xA000..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F | 0xFF00..=0xFF60 | 0xFFE0..=0xFFE6
+        | 0x1F300..=0x1F64F | 0x1F900..=0x1F9FF | 0x20000..=0x3FFFD)
+}
+
+/// `ch`, or `PLACEHOLDER` if it would throw the cursor out of step.
+fn printable(ch: char) -> char {
+    if misfit(ch) { PLACEHOLDER } else { ch }
+}
+
+/// A double-buffered renderer. The back buffer is whatever `Canvas` is
+/// passed to `render`; the front buffer is what the terminal shows now.
+#[derive(Clone, Debug)]
+pub struct Terminal {
+    mode: Mode,
+    /// The last frame drawn, with its width and height; `None` before the
+    /// first frame or after `invalidate`.
+    front: Option<(i32, i32, Vec<Cell>)>,
+}
+
+impl Terminal {
+    pub fn new(mode: Mode) -> Terminal {
+        Terminal { mode, front: None }
+    }
+
+    pub fn mode(&self) -> Mode {
+        self.mode
+    }
+
+    /// Forget what's on screen, so the next frame is drawn in full: call
+    /// this if something else may have written to the terminal.
+    pub fn invalidate(&mut self) {
+        self.front = None;
+    }
+
+    /// Show `canvas`, writing as little as possible to `out`. Characters
+    /// that wouldn't fill exactly one cell are shown as `PLACEHOLDER`.
+    pub fn render<W: Write + ?Sized>(&mut self, canvas: &Canvas, out: &mut W) -> io::Result<()> {
+        let (width, height) = (canvas.width(), canvas.height());
+        let back: Vec<Cell> = (0..height).flat_map(|y| canvas.row(y)).collect();
+        match self.mode {
+            Mode::Plain => {
+                for y in 0..height {
+                    let line: String = canvas.row(y).map(|c| printable(c.ch)).collect();
+                    writeln!(out, "{}", line)?;
+                }
+            }
+            Mode::Ansi => {
+                let front = match &self.front {
+                    Some((w, h, cells)) if (*w, *h) == (width, height) => Some(cells),
+                    _ => None,
+                };
+                if front.is_none() {
+                    out.write_all(b"\x1b[0m\x1b[2J")?;
+                }
+                write_diff(front.map(|f| &f[..]), &back, width, out)?;
+            }
+        }
+        out.flush()?;
+        self.front = Some((width, height, back));
+        Ok(())
+    }
+}
+
+/// Write the cells of `back` that differ from `front` (all of them, with no
+/// `front`). Colours start as the defaults and are reset at the end.
+fn write_diff<W: Write + ?Sized>(front: Option<&[Cell]>, back: &[Cell], width: i32, out: &mut W) -> io::Result<()> {
+    let mut cursor = None;
+    let (mut fg, mut bg) = (Color::Default, Color::Default);
+    for (i, cell) in back.iter().enumerate() {
+        if front.is_some_and(|f| f[i] == *cell) {
+            continue;
+        }
+        let (x, y) = (i as i32 % width, i as i32 / width);
+        if cursor != Some((x, y)) {
+            write!(out, "\x1b[{};{}H", y + 1, x + 1)?;
+        }
+        match (cell.fg != fg, cell.bg != bg) {
+            (true, true) => write!(out, "\x1b[{};{}m", sgr(cell.fg), sgr(cell.bg) + 10)?,
+            (true, false) => write!(out, "\x1b[{}m", sgr(cell.fg))?,
+            (false, true) => write!(out, "\x1b[{}m", sgr(cell.bg) + 10)?,
+            (false, false) => {}
+        }
+        fg = cell.fg;
+        bg = cell.bg;
+        write!(out, "{}", printable(cell.ch))?;
+        // At the right edge the cursor stays put or wraps depending on the
+        // terminal, so don't rely on it.
+        cursor = if x + 1 < width { Some((x + 1, y)) } else { None };
+    }
+    if (fg, bg) != (Color::Default, Color::Default) {
+        out.write_all(b"\x1b[0m")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(terminal: &mut Terminal, canvas: &Canvas) -> String {
+        let mut bytes = vec![];
+        terminal.render(canvas, &mut bytes).unwrap();
+        String::from_utf8(bytes).unwrap()
+    }
+
+    #[test]
+    fn test_minimal_diffs() {
+        let mut terminal = Terminal::new(Mode::Ansi);
+        let mut canvas = Canvas::new(3, 2);
+        canvas.write_str(0, 0, "ab");
+        assert_eq!(render(&mut terminal, &canvas), "\x1b[0m\x1b[2J\x1b[1;1Hab \x1b[2;1H   ");
+
+        // Nothing changed, nothing sent.
+        assert_eq!(render(&mut terminal, &canvas), "");
+
+        canvas.set_colors(Color::Red, Color::Default);
+        canvas.write_at(1, 0, 'X');
+        canvas.set_colors(Color::Red, Color::Blue);
+        canvas.write_at(2, 0, 'Y');
+        canvas.set_colors(Color::Default, Color::Default);
+        canvas.write_at(2, 1, '!');
+        assert_eq!(render(&mut terminal, &canvas),
+                   "\x1b[1;2H\x1b[31mX\x1b[44mY\x1b[2;3H\x1b[39;49m!");
+
+        // A new size means a full redraw.
+        let canvas = Canvas::new(1, 1);
+        assert_eq!(render(&mut terminal, &canvas), "\x1b[0m\x1b[2J\x1b[1;1H ");
+        terminal.invalidate();
+        assert!(render(&mut terminal, &canvas).contains("\x1b[2J"));
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let mut canvas = Canvas::new(6, 1);
+        canvas.write_str(0, 0, "\x1b[2J\n世");
+        let mut terminal = Terminal::new(Mode::Ansi);
+        assert_eq!(render(&mut terminal, &canvas), "\x1b[0m\x1b[2J\x1b[1;1H?[2J??");
+        // The cursor is where it's thought to be, so the next diff lands.
+        canvas.write_at(5, 0, 'x');
+        assert_eq!(render(&mut terminal, &canvas), "\x1b[1;6Hx");
+
+        let mut terminal = Terminal::new(Mode::Plain);
+        assert_eq!(render(&mut terminal, &canvas), "?[2J?x\n");
+    }
+
+    #[test]
+    fn test_plain() {
+        let mut terminal = Terminal::new(Mode::Plain);
+        let mut canvas = Canvas::new(3, 2);
+        canvas.set_colors(Color::Green, Color::Default);
+        canvas.write_str(0, 1, "hi");
+        assert_eq!(render(&mut terminal, &canvas), "   \nhi \n");
+        assert_eq!(render(&mut terminal, &canvas), "   \nhi \n");
+    }
+}
@@ -0,0 +1,321 @@
+//! A vantage-point tree: an index for any metric `MeasureDistance` type,
+//! needing nothing but the distance function itself.
+
+use std::ops::Add;
+
+use crate::distance::{compare_distances, resolve, scapegoat, too_deep, Collector, KBest,
+                      MeasureDistance, Within};
+
+#[derive(Clone, Debug)]
+struct Node<D> {
+    /// Index into `VpTree::points`.
+    point: usize,
+    /// Points closer to this one than `threshold` go inside, further ones
+    /// outside, and ones exactly that far on either side. `None` until the
+    /// node has children.
+    threshold: Option<D>,
+    inside: Option<usize>,
+    outside: Option<usize>,
+    /// How many points are in this subtree, this one included.
+    size: usize,
+}
+
+/// A vantage-point tree. Each node picks a point and a radius around it;
+/// everything beneath the node is either inside or outside that ball, and
+/// the triangle inequality tells a search which side it can skip.
+///
+/// The distance type needs `Add` for that arithmetic, which `f64` and
+/// `usize` both have. As with `KdTree`, `bulk_load` builds a balanced tree,
+/// `insert` rebuilds any subtree it leaves too lopsided, and queries give
+/// exactly the brute-force answers on the points in insertion order.
+#[derive(Clone, Debug)]
+pub struct VpTree<P: MeasureDistance> {
+    points: Vec<P>,
+    nodes: Vec<Node<P::Distance>>,
+    root: Option<usize>,
+}
+
+impl<P> Default for VpTree<P>
+    where P: MeasureDistance,
+          P::Distance: Add<Output=P::Distance>
+{
+    fn default() -> Self {
+        VpTree::new()
+    }
+}
+
+impl<P> VpTree<P>
+    where P: MeasureDistance,
+          P::Distance: Add<Output=P::Distance>
+{
+    pub fn new() -> VpTree<P> {
+        VpTree { points: vec![], nodes: vec![], root: None }
+    }
+
+    /// Build a balanced tree over `points`. Each node's threshold is the
+    /// median distance from it to the points beneath it, and the points
+    /// split evenly around it, however many share that distance.
+    pub fn bulk_load(points: Vec<P>) -> VpTree<P> {
+        let mut tree = VpTree { points, nodes: vec![], root: None };
+        let order: Vec<usize> = (0..tree.points.len()).collect();
+        tree.root = tree.build(order, &mut vec![]);
+        tree
+    }
+
+    /// Build a balanced subtree over the points in `order`, reusing the
+    /// nodes in `slots` before adding new ones.
+    fn build(&mut self, mut order: Vec<usize>, slots: &mut Vec<usize>) -> Option<usize> {
+        if order.is_empty() {
+            return None;
+        }
+        let size = order.len();
+        // The first point is as good a vantage point as any, and picking it
+        // keeps the build deterministic.
+        let vantage = order.remove(0);
+        let mut node = Node { point: vantage, threshold: None, inside: None, outside: None, size };
+
+        if !order.is_empty() {
+            let point = &self.points[vantage];
+            let mut by_distance: Vec<(P::Distance, usize)> = order.into_iter()
+                .map(|i| (point.distance(&self.points[i]), i))
+                .collect();
+            by_distance.sort_by(|a, b| compare_distances(&a.0, &b.0).then(a.1.cmp(&b.1)));
+            let mid = by_distance.len() / 2;
+            let threshold = by_distance[mid].0;
+
+            let outside: Vec<usize> = by_distance.drain(mid..).map(|(_, i)| i).collect();
+            let inside: Vec<usize> = by_distance.into_iter().map(|(_, i)| i).collect();
+            node.threshold = Some(threshold);
+            node.inside = self.build(inside, slots);
+            node.outside = self.build(outside, slots);
+        }
+
+        match slots.pop() {
+            Some(slot) => {
+                self.nodes[slot] = node;
+                Some(slot)
+            }
+            None => {
+                self.nodes.push(node);
+                Some(self.nodes.len() - 1)
+            }
+        }
+    }
+
+    /// Rebuild the subtree at `path[at]`, where `path` runs down from the
+    /// root.
+    fn rebuild(&mut self, path: &[usize], at: usize) {
+        let mut slots = vec![];
+        let mut order = vec![];
+        let mut stack = vec![path[at]];
+        while let Some(n) = stack.pop() {
+            let node = &self.nodes[n];
+            slots.push(n);
+            order.push(node.point);
+            stack.extend(node.inside.into_iter().chain(node.outside));
+        }
+        order.sort_unstable();
+        let new = self.build(order, &mut slots);
+        match at.checked_sub(1) {
+            None => self.root = new,
+            Some(i) => {
+                let parent = &mut self.nodes[path[i]];
+                if parent.inside == Some(path[at]) {
+                    parent.inside = new;
+                } else {
+                    parent.outside = new;
+                }
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// The points in insertion order.
+    pub fn points(&self) -> &[P] {
+        &self.points
+    }
+
+    pub fn insert(&mut self, point: P) {
+        let index = self.points.len();
+        let mut link = self.root;
+        let mut path = vec![];
+        let mut go_inside = false;
+        while let Some(n) = link {
+            let d = point.distance(&self.points[self.nodes[n].point]);
+            let node = &mut self.nodes[n];
+            node.size += 1;
+            // A childless node takes its first child's distance as its
+            // threshold, which puts that child outside.
+            let threshold = *node.threshold.get_or_insert(d);
+            go_inside = d < threshold;
+            path.push(n);
+            link = if go_inside { node.inside } else { node.outside };
+        }
+
+        let depth = path.len();
+        self.points.push(point);
+        self.nodes.push(Node { point: index, threshold: None, inside: None, outside: None, size: 1 });
+        let new = self.nodes.len() - 1;
+        match path.last() {
+            None => self.root = Some(new),
+            Some(&p) if go_inside => self.nodes[p].inside = Some(new),
+            Some(&p) => self.nodes[p].outside = Some(new),
+        }
+        path.push(new);
+
+        if too_deep(depth, self.points.len()) {
+            let at = scapegoat(&path, |n| self.nodes[n].size);
+            self.rebuild(&path, at);
+        }
+    }
+
+    /// The closest point to `target`, with its distance.
+    pub fn nearest(&self, target: &P) -> Option<(&P, P::Distance)> {
+        self.k_nearest(target, 1).into_iter().next()
+    }
+
+    /// The `k` closest points to `target`, nearest first.
+    pub fn k_nearest(&self, target: &P, k: usize) -> Vec<(&P, P::Distance)> {
+        if k == 0 {
+            return vec![];
+        }
+        let mut best = KBest::new(k);
+        self.search(self.root, target, &mut best);
+        resolve(&self.points, best.into_sorted_vec())
+    }
+
+    /// Every point within `radius` of `target`, nearest first.
+    pub fn within_radius(&self, target: &P, radius: P::Distance) -> Vec<(&P, P::Distance)> {
+        let mut found = Within::new(radius);
+        self.search(self.root, target, &mut found);
+        resolve(&self.points, found.into_sorted_vec())
+    }
+
+    fn search<C>(&self, link: Option<usize>, target: &P, found: &mut C)
+        where C: Collector<P::Distance>
+    {
+        let node = match link {
+            Some(n) => &self.nodes[n],
+            None => return,
+        };
+        let d = target.distance(&self.points[node.point]);
+        found.offer(d, node.point);
+        let threshold = match node.threshold {
+            Some(t) => t,
+            None => return,
+        };
+
+        // By the triangle inequality, everything inside is more than
+        // `d - threshold` from the target, and everything outside is at
+        // least `threshold - d` away. Both comparisons are rearranged to
+        // avoid subtraction, so unsigned distances work too.
+        let inside_may_match = |bound: Option<P::Distance>| {
+            bound.is_none_or(|b| d <= threshold + b)
+        };
+        let outside_may_match = |bound: Option<P::Distance>| {
+            bound.is_none_or(|b| threshold <= d + b)
+        };
+        if d < threshold {
+            self.search(node.inside, target, found);
+            if outside_may_match(found.bound()) {
+                self.search(node.outside, target, found);
+            }
+        } else {
+            self.search(node.outside, target, found);
+            if inside_may_match(found.bound()) {
+                self.search(node.inside, target, found);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::distance::{k_nearest, nearest, within_radius, Manhattan};
+    use crate::rand::{Rng, XorShiftRng};
+
+    fn random_word(rng: &mut XorShiftRng) -> String {
+        let len = 1 + rng.below(6) as usize;
+        (0..len).map(|_| (b'a' + rng.below(4) as u8) as char).collect()
+    }
+
+    #[test]
+    fn test_matches_brute_force_on_strings() {
+        let mut rng = XorShiftRng::seed_from_u64(30);
+        for round in 0..30 {
+            let words: Vec<String> = (0..round * 5).map(|_| random_word(&mut rng)).collect();
+            let bulk = VpTree::bulk_load(words.clone());
+            let mut grown = VpTree::new();
+            for w in &words {
+                grown.insert(w.clone());
+            }
+
+            for _ in 0..20 {
+                let target = random_word(&mut rng);
+                let k = rng.below(10) as usize;
+                let radius = rng.below(4) as usize;
+                for tree in [&bulk, &grown] {
+                    assert_eq!(tree.nearest(&target), nearest(&target, &words));
+                    assert_eq!(tree.k_nearest(&target, k), k_nearest(&target, &words, k));
+                    assert_eq!(tree.within_radius(&target, radius),
+                               within_radius(&target, &words, radius));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_matches_brute_force_on_points() {
+        let mut rng = XorShiftRng::seed_from_u64(31);
+        let points: Vec<_> = (0..500)
+            .map(|_| Manhattan([rng.below(50) as i32, rng.below(50) as i32]))
+            .collect();
+        let tree = VpTree::bulk_load(points.clone());
+        for _ in 0..100 {
+            let target = Manhattan([rng.below(50) as i32, rng.below(50) as i32]);
+            assert_eq!(tree.k_nearest(&target, 5), k_nearest(&target, &points, 5));
+            assert_eq!(tree.within_radius(&target, 6.0), within_radius(&target, &points, 6.0));
+        }
+    }
+
+    /// The most nodes on any path down from the root.
+    fn depth<P: MeasureDistance>(tree: &VpTree<P>) -> usize {
+        let mut deepest = 0;
+        let mut stack: Vec<(usize, usize)> = tree.root.map(|root| (root, 1)).into_iter().collect();
+        while let Some((n, d)) = stack.pop() {
+            deepest = deepest.max(d);
+            let node = &tree.nodes[n];
+            stack.extend(node.inside.into_iter().chain(node.outside).map(|child| (child, d + 1)));
+        }
+        deepest
+    }
+
+    #[test]
+    fn test_stays_shallow() {
+        // Sorted points and copies of one point would both make a chain of
+        // a tree without rebalancing.
+        let sorted: Vec<_> = (0..20_000).map(|i| Manhattan([i, i])).collect();
+        let copies = vec![Manhattan([3, 4]); 20_000];
+        for points in [sorted, copies] {
+            let bulk = VpTree::bulk_load(points.clone());
+            let mut grown = VpTree::new();
+            for p in &points {
+                grown.insert(*p);
+            }
+            let target = Manhattan([100, 90]);
+            for tree in [&bulk, &grown] {
+                // Log base 1 / 0.7 of 20,000 is under 28.
+                assert!(depth(tree) <= 32, "{} deep", depth(tree));
+                assert_eq!(tree.k_nearest(&target, 3), k_nearest(&target, &points, 3));
+            }
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Event-time windowed aggregation over unbounded streams, reusing the same
+//! `Reducer` implementations as `run_query`.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{self, Write};
+
+use crate::mapreduce::{Key, Reducer, Row};
+
+/// Event time, in whatever unit the stream uses (usually milliseconds).
+pub type Time = i64;
+
+/// One record of the stream.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub time: Time,
+    pub key: Key,
+    pub values: Row,
+}
+
+/// How events are grouped into windows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WindowSpec {
+    /// Back-to-back windows `[n * size, (n + 1) * size)`.
+    Tumbling { size: Time },
+    /// Windows of length `size` starting every `slide`; an event can fall
+    /// into several of them, up to `MAX_WINDOWS_PER_EVENT`, or, if `slide`
+    /// is longer than `size`, into none.
+    Sliding { size: Time, slide: Time },
+    /// Per-key windows that stay open while events keep arriving at most
+    /// `gap` apart.
+    Session { gap: Time },
+}
+
+/// A finished window's result.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowResult {
+    pub start: Time,
+    pub end: Time,
+    pub key: Key,
+    pub row: Row,
+}
+
+impl WindowResult {
+    /// Write this result as one tab-separated line: start, end, the key
+    /// columns, then the reducer's output columns.
+    pub fn write_to<W: Write + ?Sized>(&self, out: &mut W) -> io::Result<()> {
+        write!(out, "{}\t{}", self.start, self.end)?;
+        for v in self.key.iter().chain(&self.row) {
+            write!(out, "\t{}", v)?;
+        }
+        writeln!(out)
+    }
+}
+
+/// The most sliding windows one event can fall into, so a tiny slide can't
+/// turn each event into an unbounded amount of work.
+pub const MAX_WINDOWS_PER_EVENT: Time = 1024;
+
+struct Open<A> {
+    end: Time,
+    acc: A,
+}
+
+/// Aggregate a stream of `Event`s into windows, writing each window's result
+/// to `sink` once the watermark passes its end.
+///
+/// The watermark trails the largest event time seen by `max_delay`, so events
+/// may arrive up to that much out of order. Anything later than that, for a
+/// window that has already been emitted, is set aside as late data.
+pub struct WindowedAggregator<R: Reducer, W: Write> {
+    spec: WindowSpec,
+    reducer: R,
+    sink: W,
+    max_delay: Time,
+    watermark: Time,
+    /// Open windows, keyed by (key, window start).
+    open: BTreeMap<(Key, Time), Open<R::Acc>>,
+    /// The same windows ordered by when they're due: (end, key, start).
+    due: BTreeSet<(Time, Key, Time)>,
+    late: Vec<Event>,
+}
+
+impl<R: Reducer, W: Write> WindowedAggregator<R, W> {
+    pub fn new(spec: WindowSpec, reducer: R, sink: W) -> WindowedAggregator<R, W> {
+        match spec {
+            WindowSpec::Tumbling { size } => assert!(size > 0, "window size must be positive"),
+            WindowSpec::Sliding { size, slide } => {
+                assert!(size > 0 && slide > 0, "window size and slide must be positive");
+                assert!(size / slide < MAX_WINDOWS_PER_EVENT,
+                        "sliding windows can't overlap more than {} deep", MAX_WINDOWS_PER_EVENT);
+            }
+            WindowSpec::Session { gap } => assert!(gap > 0, "session gap must be positive"),
+        }
+        WindowedAggregator {
+            spec,
+            reducer,
+            sink,
+            max_delay: 0,
+            watermark: Time::MIN,
+            open: BTreeMap::new(),
+            due: BTreeSet::new(),
+            late: vec![],
+        }
+    }
+
+    /// Tolerate events arriving up to `delay` behind the newest one seen.
+    pub fn with_max_delay(mut self, delay: Time) -> WindowedAggregator<R, W> {
+        assert!(delay >= 0, "max delay can't be negative");
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn watermark(&self) -> Time {
+        self.watermark
+    }
+
+    /// Take the events that arrived too late to be counted.
+    pub fn take_late(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.late)
+    }
+
+    /// Feed one event, emitting any windows the new watermark closes.
+    pub fn push(&mut self, event: Event) -> io::Result<()> {
+        let time = event.time;
+        let accepted = match self.spec {
+            WindowSpec::Tumbling { size } => self.add_fixed(&event, size, size),
+            WindowSpec::Sliding { size, slide } => self.add_fixed(&event, size, slide),
+            WindowSpec::Session { gap } => self.add_session(&event, gap),
+        };
+        if !accepted {
+            self.late.push(event);
+        }
+        self.advance_watermark(time.saturating_sub(self.max_delay))
+    }
+
+    /// Move the watermark forward to `time`, emitting every window that ends
+    /// at or before it. Useful when the stream goes quiet.
+    pub fn advance_watermark(&mut self, time: Time) -> io::Result<()> {
+        if time <= self.watermark {
+            return Ok(());
+        }
+        self.watermark = time;
+        while let Some(first) = self.due.iter().next().cloned() {
+            if first.0 > self.watermark {
+                break;
+            }
+            self.due.remove(&first);
+            self.emit(first.1, first.2)?;
+        }
+        Ok(())
+    }
+
+    /// End of stream: emit every window still open and return the sink.
+    pub fn finish(mut self) -> io::Result<W> {
+        self.advance_watermark(Time::MAX)?;
+        self.sink.flush()?;
+        Ok(self.sink)
+    }
+
+    fn emit(&mut self, key: Key, start: Time) -> io::Result<()> {
+        let window = self.open.remove(&(key.clone(), start))
+            .expect("due window must be open");
+        let result = WindowResult {
+            start,
+            end: window.end,
+            key,
+            row: self.reducer.finish(window.acc),
+        };
+        result.write_to(&mut self.sink)
+    }
+
+    /// Add an event to every tumbling or sliding window covering its time.
+    /// Returns false if all of those windows have already been emitted; an
+    /// event in a gap between sliding windows isn't late, just dropped.
+    fn add_fixed(&mut self, event: &Event, size: Time, slide: Time) -> bool {
+        // The latest window containing `time` starts at or before it, on a
+        // multiple of `slide`; earlier ones follow at `slide` intervals.
+        // Worked out in i128, then clipped to the range of `Time`: windows
+        // that would run past the end of time stop there, and the first
+        // window starting before the beginning of time starts there instead.
+        let (time, size, slide) = (event.time as i128, size as i128, slide as i128);
+        let mut start = time.div_euclid(slide) * slide;
+        let mut covered = false;
+        let mut accepted = false;
+        while start + size > time {
+            covered = true;
+            let end = (start + size).min(Time::MAX as i128) as Time;
+            if end > self.watermark {
+                let clipped = start.max(Time::MIN as i128) as Time;
+                self.window(event.key.clone(), clipped, end, |reducer, acc| {
+                    reducer.step(acc, &event.values)
+                });
+                accepted = true;
+            }
+            if start <= Time::MIN as i128 {
+                break;
+            }
+            start -= slide;
+        }
+        accepted || !covered
+    }
+
+    fn add_session(&mut self, event: &Event, gap: Time) -> bool {
+        let mut start = event.time;
+        let mut end = event.time.saturating_add(gap);
+
+        // Pull out every session for this key that the new event touches.
+        let touching: Vec<Time> = self.open
+            .range((event.key.clone(), Time::MIN)..=(event.key.clone(), end))
+            .filter(|(_, w)| w.end >= event.time)
+            .map(|(&(_, s), _)| s)
+            .collect();
+        if touching.is_empty() && end <= self.watermark {
+            return false;
+        }
+
+        let mut acc = self.reducer.start();
+        self.reducer.step(&mut acc, &event.values);
+        for s in touching {
+            let old = self.open.remove(&(event.key.clone(), s)).unwrap();
+            self.due.remove(&(old.end, event.key.clone(), s));
+            self.reducer.merge(&mut acc, old.acc);
+            start = start.min(s);
+            end = end.max(old.end);
+        }
+        self.due.insert((end, event.key.clone(), start));
+        self.open.insert((event.key.clone(), start), Open { end, acc });
+        true
+    }
+
+    fn window<F>(&mut self, key: Key, start: Time, end: Time, update: F)
+        where F: FnOnce(&R, &mut R::Acc)
+    {
+        let reducer = &self.reducer;
+        let due = &mut self.due;
+        let window = self.open.entry((key.clone(), start)).or_insert_with(|| {
+            due.insert((end, key, start));
+            Open { end, acc: reducer.start() }
+        });
+        update(reducer, &mut window.acc);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mapreduce::{Aggregate, AggregateFn};
+
+    fn event(time: Time, key: &str, value: i64) -> Event {
+        Event { time, key: vec![key.into()], values: vec![value.into()] }
+    }
+
+    fn sum() -> Vec<Aggregate> {
+        vec![Aggregate { func: AggregateFn::Count, input: None },
+             Aggregate { func: AggregateFn::Sum, input: Some(0) }]
+    }
+
+    fn run(spec: WindowSpec, delay: Time, events: Vec<Event>) -> (String, Vec<Event>) {
+        let mut agg = WindowedAggregator::new(spec, sum(), vec![]).with_max_delay(delay);
+        for e in events {
+            agg.push(e).unwrap();
+        }
+        let late = agg.take_late();
+        (String::from_utf8(agg.finish().unwrap()).unwrap(), late)
+    }
+
+    #[test]
+    fn test_tumbling_with_late_data() {
+        let (out, late) = run(WindowSpec::Tumbling { size: 10 }, 5, vec![
+            event(1, "a", 1),
+            event(12, "a", 2),
+            event(8, "a", 4), // out of order, but within the delay
+            event(30, "a", 8),
+            event(3, "a", 16), // window [0, 10) was emitted at watermark 25
+        ]);
+        assert_eq!(out, "0\t10\ta\t2\t5\n10\t20\ta\t1\t2\n30\t40\ta\t1\t8\n");
+        assert_eq!(late, vec![event(3, "a", 16)]);
+    }
+
+    #[test]
+    fn test_sliding_and_session() {
+        let (out, _) = run(WindowSpec::Sliding { size: 10, slide: 5 }, 0,
+                           vec![event(7, "a", 1)]);
+        assert_eq!(out, "0\t10\ta\t1\t1\n5\t15\ta\t1\t1\n");
+
+        // Between windows, an event on time counts nowhere but isn't late.
+        let (out, late) = run(WindowSpec::Sliding { size: 5, slide: 10 }, 0,
+                              vec![event(3, "a", 1), event(7, "a", 2), event(12, "a", 4)]);
+        assert_eq!(out, "0\t5\ta\t1\t1\n10\t15\ta\t1\t4\n");
+        assert_eq!(late, []);
+
+        let (out, _) = run(WindowSpec::Session { gap: 5 }, 100, vec![
+            event(10, "a", 1),
+            event(20, "a", 2),
+            event(15, "a", 4), // bridges the two sessions
+            event(11, "b", 8),
+        ]);
+        assert_eq!(out, "11\t16\tb\t1\t8\n10\t25\ta\t3\t7\n");
+
+        // Exactly `gap` apart still counts as the same session.
+        let (out, _) = run(WindowSpec::Session { gap: 5 }, 0, vec![
+            event(10, "a", 1),
+            event(15, "a", 2),
+            event(21, "a", 4),
+        ]);
+        assert_eq!(out, "10\t20\ta\t2\t3\n21\t26\ta\t1\t4\n");
+    }
+
+    #[test]
+    #[should_panic(expected = "overlap")]
+    fn test_too_many_windows() {
+        WindowedAggregator::new(WindowSpec::Sliding { size: Time::MAX, slide: 1 }, sum(), vec![]);
+    }
+
+    #[test]
+    fn test_end_of_time() {
+        let (out, _) = run(WindowSpec::Session { gap: 5 }, 0, vec![event(Time::MAX - 2, "a", 1)]);
+        assert_eq!(out, format!("{}\t{}\ta\t1\t1\n", Time::MAX - 2, Time::MAX));
+
+        let (out, _) = run(WindowSpec::Sliding { size: 10, slide: 5 }, 0,
+                           vec![event(Time::MAX - 3, "a", 1)]);
+        let start = Time::MAX - 7;
+        assert_eq!(out, format!("{}\t{}\ta\t1\t1\n{}\t{}\ta\t1\t1\n",
+                                start - 5, Time::MAX - 2, start, Time::MAX));
+
+        let (out, _) = run(WindowSpec::Tumbling { size: 10 }, 0, vec![event(Time::MIN, "a", 1)]);
+        assert_eq!(out, format!("{}\t{}\ta\t1\t1\n", Time::MIN, Time::MIN + 8));
+    }
+}
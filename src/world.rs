@@ -0,0 +1,454 @@
+//! A `World` that moves `Creature`s around a map, one fixed timestep at a
+//! time.
+//!
+//! Everything random, such as the order in which creatures move each tick
+//! and the choices their behaviours make, comes from a generator seeded when
+//! the world is made, so the same seed and the same calls always play out
+//! the same way.
+
+use crate::behavior::{Action, Behavior, WorldView};
+use crate::canvas::Canvas;
+use crate::game::{Creature, Direction, Visible};
+use crate::geometry::{Point, Rect};
+use crate::rand::{Rng, XorShiftRng};
+use crate::spatial::SpatialHash;
+
+/// A handle to a creature in a `World`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CreatureId(usize);
+
+/// A handle to a fixed obstacle in a `World`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ObstacleId(usize);
+
+/// Something a creature can bump into.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Body {
+    Creature(CreatureId),
+    Obstacle(ObstacleId),
+}
+
+/// Something that happened during a tick.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// `creature` tried to step into `with` and stayed where it was.
+    Collided { creature: CreatureId, with: Body },
+    /// `creature` walked off the edge of the map and was removed.
+    LeftMap { creature: CreatureId },
+}
+
+/// Do `a` and `b` share any cell, going by their `hit_test`s?
+pub fn overlaps(a: &dyn Visible, b: &dyn Visible) -> bool {
+    let both = match a.bounds().intersection(&b.bounds()) {
+        Some(rect) => rect,
+        None => return false,
+    };
+    (both.min.y() ..= both.max.y()).any(|y| {
+        (both.min.x() ..= both.max.x()).any(|x| a.hit_test(x, y) && b.hit_test(x, y))
+    })
+}
+
+struct Mover {
+    creature: Box<dyn Creature>,
+    /// In cells per second.
+    speed: f64,
+    /// Fraction of a step built up over past ticks.
+    progress: f64,
+    brain: Option<Box<dyn Behavior>>,
+}
+
+/// The default timestep: ten ticks a second.
+pub const DEFAULT_TIMESTEP: f64 = 0.1;
+
+/// Slack for rounding when adding up fractions of a step or a tick, so that
+/// ten ticks of 0.1 seconds make a whole second.
+const EPSILON: f64 = 1e-9;
+
+/// A `width` by `height` map of creatures and obstacles.
+///
+/// Each tick, every creature, in a random order, advances by its speed in
+/// the direction it's facing, one cell at a time. A step that would overlap
+/// another creature or an obstacle isn't taken; one that takes a creature's
+/// position off the map removes the creature.
+pub struct World {
+    width: i32,
+    height: i32,
+    timestep: f64,
+    ticks: u64,
+    /// Time passed to `advance` that hasn't made up a whole tick yet.
+    pending: f64,
+    rng: XorShiftRng,
+    creatures: Vec<Option<Mover>>,
+    obstacles: Vec<Box<dyn Visible>>,
+    index: SpatialHash<Body>,
+}
+
+impl World {
+    /// An empty `width` by `height` world. Panics if either size is
+    /// negative.
+    pub fn new(width: i32, height: i32, seed: u64) -> World {
+        assert!(width >= 0 && height >= 0, "world size can't be negative");
+        World {
+            width,
+            height,
+            timestep: DEFAULT_TIMESTEP,
+            ticks: 0,
+            pending: 0.0,
+            rng: XorShiftRng::seed_from_u64(seed),
+            creatures: vec![],
+            obstacles: vec![],
+            index: SpatialHash::new(8),
+        }
+    }
+
+    /// Use ticks `seconds` long.
+    pub fn with_timestep(mut self, seconds: f64) -> World {
+        assert!(seconds.is_finite() && seconds > 0.0, "timestep must be positive");
+        self.timestep = seconds;
+        self
+    }
+
+    pub fn width(&self) -> i32 {
+        self.width
+    }
+
+    pub fn height(&self) -> i32 {
+        self.height
+    }
+
+    pub fn timestep(&self) -> f64 {
+        self.timestep
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks
+    }
+
+    /// Simulated time so far, in seconds.
+    pub fn time(&self) -> f64 {
+        self.ticks as f64 * self.timestep
+    }
+
+    pub fn on_map(&self, x: i32, y: i32) -> bool {
+        0 <= x && x < self.width && 0 <= y && y < self.height
+    }
+
+    /// The world's random number generator, for anything that should replay
+    /// along with the world.
+    pub fn rng(&mut self) -> &mut XorShiftRng {
+        &mut self.rng
+    }
+
+    /// Add a creature moving at `speed` cells per second.
+    pub fn add_creature<C: Creature + 'static>(&mut self, creature: C, speed: f64) -> CreatureId {
+        self.add_creature_boxed(Box::new(creature), speed)
+    }
+
+    pub fn add_creature_boxed(&mut self, creature: Box<dyn Creature>, speed: f64) -> CreatureId {
+        let id = CreatureId(self.creatures.len());
+        self.index.update(Body::Creature(id), &*creature);
+        self.creatures.push(Some(Mover { creature, speed, progress: 0.0, brain: None }));
+        id
+    }
+
+    pub fn add_obstacle<V: Visible + 'static>(&mut self, obstacle: V) -> ObstacleId {
+        self.add_obstacle_boxed(Box::new(obstacle))
+    }
+
+    pub fn add_obstacle_boxed(&mut self, obstacle: Box<dyn Visible>) -> ObstacleId {
+        let id = ObstacleId(self.obstacles.len());
+        self.index.update(Body::Obstacle(id), &*obstacle);
+        self.obstacles.push(obstacle);
+        id
+    }
+
+    pub fn remove_creature(&mut self, id: CreatureId) -> Option<Box<dyn Creature>> {
+        let mover = self.creatures.get_mut(id.0)?.take()?;
+        self.index.remove(Body::Creature(id));
+        Some(mover.creature)
+    }
+
+    pub fn creature(&self, id: CreatureId) -> Option<&dyn Creature> {
+        self.creatures.get(id.0)?.as_ref().map(|m| &*m.creature)
+    }
+
+    /// Every creature still in the world.
+    pub fn creatures(&self) -> impl Iterator<Item=(CreatureId, &dyn Creature)> {
+        self.creatures.iter().enumerate()
+            .filter_map(|(i, m)| m.as_ref().map(|m| (CreatureId(i), &*m.creature)))
+    }
+
+    pub fn obstacle(&self, id: ObstacleId) -> Option<&dyn Visible> {
+        self.obstacles.get(id.0).map(|o| &**o)
+    }
+
+    pub fn obstacles(&self) -> impl Iterator<Item=(ObstacleId, &dyn Visible)> {
+        self.obstacles.iter().enumerate().map(|(i, o)| (ObstacleId(i), &**o))
+    }
+
+    pub fn body(&self, body: Body) -> Option<&dyn Visible> {
+        match body {
+            Body::Creature(id) => self.creature(id).map(|c| c as &dyn Visible),
+            Body::Obstacle(id) => self.obstacle(id),
+        }
+    }
+
+    pub fn speed(&self, id: CreatureId) -> Option<f64> {
+        self.creatures.get(id.0)?.as_ref().map(|m| m.speed)
+    }
+
+    pub fn set_speed(&mut self, id: CreatureId, speed: f64) {
+        if let Some(Some(m)) = self.creatures.get_mut(id.0) {
+            m.speed = speed;
+        }
+    }
+
+    pub fn turn(&mut self, id: CreatureId, facing: Direction) {
+        if let Some(Some(m)) = self.creatures.get_mut(id.0) {
+            m.creature.turn_to(facing);
+        }
+    }
+
+    /// Let `behavior` steer `id` from now on, replacing any behaviour it
+    /// had. Without one, a creature keeps going the way it's facing.
+    pub fn set_behavior<B: Behavior + 'static>(&mut self, id: CreatureId, behavior: B) {
+        self.set_behavior_boxed(id, Box::new(behavior));
+    }
+
+    pub fn set_behavior_boxed(&mut self, id: CreatureId, behavior: Box<dyn Behavior>) {
+        if let Some(Some(m)) = self.creatures.get_mut(id.0) {
+            m.brain = Some(behavior);
+        }
+    }
+
+    /// Could `id` take a step towards `direction` right now, without leaving
+    /// the map or landing on anything?
+    pub fn can_move(&self, id: CreatureId, direction: Direction) -> bool {
+        let me = match self.creature(id) {
+            Some(me) => me,
+            None => return false,
+        };
+        let (x, y) = me.position();
+        let (dx, dy) = direction.delta();
+        if !self.on_map(x + dx, y + dy) {
+            return false;
+        }
+        let bounds = me.bounds();
+        let moved = Rect::new(Point::xy(bounds.min.x() + dx, bounds.min.y() + dy),
+                              Point::xy(bounds.max.x() + dx, bounds.max.y() + dy));
+        let others = self.bodies_in(&moved, Some(Body::Creature(id)));
+        (moved.min.y() ..= moved.max.y()).all(|y| {
+            (moved.min.x() ..= moved.max.x()).all(|x| {
+                !me.hit_test(x - dx, y - dy)
+                || !others.iter().any(|&b| self.body(b).unwrap().hit_test(x, y))
+            })
+        })
+    }
+
+    /// Every body other than `except` that overlaps `area`, going by bounds
+    /// alone.
+    pub fn bodies_in(&self, area: &Rect, except: Option<Body>) -> Vec<Body> {
+        let mut found = self.index.query_rect(area);
+        found.retain(|&b| Some(b) != except);
+        found
+    }
+
+    /// The first body, other than `id` itself, that `id` overlaps.
+    fn collision(&self, id: CreatureId) -> Option<Body> {
+        let me = self.creature(id)? as &dyn Visible;
+        self.bodies_in(&me.bounds(), Some(Body::Creature(id))).into_iter()
+            .find(|&b| overlaps(me, self.body(b).unwrap()))
+    }
+
+    /// Take one step the way `id` is facing, if nothing's in the way.
+    fn step(&mut self, id: CreatureId) -> Option<Event> {
+        let creature = &mut self.creatures[id.0].as_mut().unwrap().creature;
+        let (x, y) = creature.position();
+        let (dx, dy) = creature.facing().delta();
+        creature.move_to(x + dx, y + dy);
+
+        if !self.on_map(x + dx, y + dy) {
+            self.remove_creature(id);
+            return Some(Event::LeftMap { creature: id });
+        }
+        if let Some(with) = self.collision(id) {
+            self.creatures[id.0].as_mut().unwrap().creature.move_to(x, y);
+            return Some(Event::Collided { creature: id, with });
+        }
+        let creature = &self.creatures[id.0].as_ref().unwrap().creature;
+        self.index.update(Body::Creature(id), &**creature);
+        None
+    }
+
+    /// Ask `id`'s behaviour, if it has one, what to do this tick.
+    fn think(&mut self, id: CreatureId) -> Option<Action> {
+        let mut brain = self.creatures[id.0].as_mut()?.brain.take()?;
+        // The view borrows the whole world, so the behaviour gets a copy of
+        // the generator, which then replaces the original.
+        let mut rng = self.rng.clone();
+        let action = brain.decide(&WorldView::new(self, id), &mut rng);
+        self.rng = rng;
+        if let Some(mover) = &mut self.creatures[id.0] {
+            mover.brain = Some(brain);
+        }
+        action
+    }
+
+    /// Run one timestep.
+    pub fn tick(&mut self) -> Vec<Event> {
+        let mut order: Vec<CreatureId> = self.creatures().map(|(id, _)| id).collect();
+        for i in (1..order.len()).rev() {
+            let j = self.rng.below(i as u64 + 1) as usize;
+            order.swap(i, j);
+        }
+
+        let mut events = vec![];
+        for id in order {
+            match self.think(id) {
+                Some(Action::Move(direction)) => self.turn(id, direction),
+                Some(Action::Wait) => continue,
+                None => {}
+            }
+            let timestep = self.timestep;
+            let mover = match &mut self.creatures[id.0] {
+                Some(mover) => mover,
+                None => continue,
+            };
+            mover.progress += mover.speed * timestep;
+            while self.creatures[id.0].as_ref().is_some_and(|m| m.progress >= 1.0 - EPSILON) {
+                self.creatures[id.0].as_mut().unwrap().progress -= 1.0;
+                if let Some(event) = self.step(id) {
+                    if let Some(mover) = &mut self.creatures[id.0] {
+                        mover.progress = 0.0;
+                    }
+                    events.push(event);
+                    break;
+                }
+            }
+        }
+        self.ticks += 1;
+        events
+    }
+
+    /// Let `seconds` of real time pass, running as many whole ticks as fit.
+    /// Leftover time carries over to the next call. Panics if `seconds` is
+    /// negative, infinite or NaN.
+    pub fn advance(&mut self, seconds: f64) -> Vec<Event> {
+        assert!(seconds.is_finite() && seconds >= 0.0, "can't advance the world by {} seconds", seconds);
+        self.pending += seconds;
+        let mut events = vec![];
+        while self.pending >= self.timestep - EPSILON {
+            self.pending -= self.timestep;
+            events.extend(self.tick());
+        }
+        events
+    }
+
+    /// Draw the obstacles, then the creatures on top.
+    pub fn draw(&self, canvas: &mut Canvas) {
+        for (_, obstacle) in self.obstacles() {
+            obstacle.draw(canvas);
+        }
+        for (_, creature) in self.creatures() {
+            creature.draw(canvas);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::Broom;
+
+    type Run = (Vec<(u64, Event)>, Vec<(i32, i32)>);
+
+    fn run(seed: u64) -> Run {
+        let mut world = World::new(20, 10, seed);
+        let mut brooms = vec![];
+        for i in 0..6 {
+            // Two columns of brooms sweeping towards each other, with one
+            // cell between them.
+            let mut broom = Broom::new(if i % 2 == 0 { 2 } else { 4 }, 2 + i, 0);
+            broom.facing = if i % 2 == 0 { Direction::East } else { Direction::West };
+            brooms.push(world.add_creature(broom, 10.0));
+        }
+        let mut log = vec![];
+        for _ in 0..5 {
+            let tick = world.ticks();
+            log.extend(world.tick().into_iter().map(|e| (tick, e)));
+        }
+        let positions = brooms.iter()
+            .map(|&id| world.creature(id).map_or((-1, -1), |c| c.position()))
+            .collect();
+        (log, positions)
+    }
+
+    #[test]
+    fn test_deterministic_replay() {
+        let (log, positions) = run(7);
+        assert_eq!(run(7), (log.clone(), positions.clone()));
+
+        // Each pair of brooms races for the cell between them. Whichever
+        // moves first gets it, and the other is blocked; brooms in
+        // neighbouring rows overlap, so they block each other too.
+        let mut collisions: Vec<(u64, Vec<(usize, usize)>)> = (0..5).map(|t| (t, vec![])).collect();
+        for (tick, event) in &log {
+            match event {
+                Event::Collided { creature, with: Body::Creature(other) } => {
+                    collisions[*tick as usize].1.push((creature.0, other.0));
+                }
+                other => panic!("unexpected {:?}", other),
+            }
+        }
+        assert_eq!(collisions, [
+            (0, vec![(3, 4), (5, 4), (2, 1), (0, 1)]),
+            (1, vec![(2, 1), (3, 4), (0, 1), (4, 3), (1, 0), (5, 4)]),
+            (2, vec![(2, 1), (4, 3), (3, 4), (0, 1), (1, 0), (5, 4)]),
+            (3, vec![(0, 1), (5, 4), (3, 4), (4, 3), (2, 1), (1, 0)]),
+            (4, vec![(5, 4), (3, 4), (2, 1), (4, 3), (0, 1), (1, 0)]),
+        ]);
+        assert_eq!(positions, [(2, 2), (3, 3), (2, 4), (4, 5), (3, 6), (4, 7)]);
+        assert_ne!((0..20).map(run).collect::<Vec<_>>().windows(2).filter(|w| w[0] != w[1]).count(), 0);
+    }
+
+    #[test]
+    fn test_obstacles_and_edges() {
+        let mut world = World::new(10, 5, 1);
+        let wall = world.add_obstacle(Broom::new(5, 4, 3));
+        let sweeper = world.add_creature(Broom::new(1, 4, 1), 2.0);
+        let mut leaver = Broom::new(9, 2, 0);
+        leaver.facing = Direction::NorthEast;
+        let leaver = world.add_creature(leaver, 1.0);
+
+        let events = world.advance(1.05);
+        assert_eq!(world.ticks(), 10);
+        assert_eq!(events, [Event::LeftMap { creature: leaver }]);
+        assert_eq!(world.creature(sweeper).unwrap().position(), (3, 4));
+
+        let events = world.advance(1.0);
+        assert_eq!(world.creature(sweeper).unwrap().position(), (4, 4));
+        assert_eq!(events[0], Event::Collided { creature: sweeper, with: Body::Obstacle(wall) });
+
+        let mut canvas = Canvas::new(10, 5);
+        world.draw(&mut canvas);
+        assert_eq!(canvas.to_string(), "     |    \n     |    \n    ||    \n    ||    \n    MM    \n");
+    }
+
+    #[test]
+    #[should_panic(expected = "can't advance")]
+    fn test_advance_forever() {
+        World::new(1, 1, 0).advance(f64::INFINITY);
+    }
+
+    #[test]
+    #[should_panic(expected = "can't advance")]
+    fn test_advance_by_nan() {
+        World::new(1, 1, 0).advance(f64::NAN);
+    }
+
+    #[test]
+    #[should_panic(expected = "negative")]
+    fn test_negative_size() {
+        World::new(-1, 5, 0);
+    }
+}